@@ -0,0 +1,227 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, prelude::*, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::answers::Answers;
+
+const DEFAULT_CACHE_DIR: &str = ".aoc-cache";
+const CACHE_FILE_NAME: &str = "answers.tsv";
+
+/// One cached answer. Stored as a tab separated line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    pub year: usize,
+    pub day: usize,
+    pub part: usize,
+    pub input_hash: u64,
+    pub version: u32,
+    pub answer: String,
+}
+
+impl CacheEntry {
+    pub fn from_str(line: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 6 {
+            return Err(format!("Expected 6 fields in cache line: {line}"));
+        }
+        let bad_field = |name: &str| format!("Bad {name} in cache line: {line}");
+        Ok(Self {
+            year: fields[0].parse().map_err(|_| bad_field("year"))?,
+            day: fields[1].parse().map_err(|_| bad_field("day"))?,
+            part: fields[2].parse().map_err(|_| bad_field("part"))?,
+            input_hash: u64::from_str_radix(fields[3], 16).map_err(|_| bad_field("hash"))?,
+            version: fields[4].parse().map_err(|_| bad_field("version"))?,
+            answer: fields[5].to_owned(),
+        })
+    }
+
+    pub fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{:016x}\t{}\t{}",
+            self.year, self.day, self.part, self.input_hash, self.version, self.answer
+        )
+    }
+
+    fn matches(&self, year: usize, day: usize, input_hash: u64, version: u32) -> bool {
+        self.year == year
+            && self.day == day
+            && self.input_hash == input_hash
+            && self.version == version
+    }
+}
+
+pub struct AnswerCache {
+    path: PathBuf,
+    entries: Vec<CacheEntry>,
+}
+
+impl AnswerCache {
+    /// The cache lives in `.aoc-cache` unless `AOC_CACHE_DIR` says otherwise.
+    pub fn default_dir() -> PathBuf {
+        env::var("AOC_CACHE_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from(DEFAULT_CACHE_DIR))
+    }
+
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(CACHE_FILE_NAME);
+        let mut entries = Vec::new();
+        if path.exists() {
+            let reader = BufReader::new(File::open(&path)?);
+            for line in reader.lines() {
+                match CacheEntry::from_str(&line?) {
+                    Ok(entry) => entries.push(entry),
+                    Err(msg) => println!("WARNING: {msg}"),
+                }
+            }
+        }
+        Ok(Self { path, entries })
+    }
+
+    pub fn entries(&self) -> &[CacheEntry] {
+        &self.entries
+    }
+
+    pub fn lookup(&self, year: usize, day: usize, input_hash: u64, version: u32) -> Answers {
+        let mut answers = Answers::default();
+        for e in self
+            .entries
+            .iter()
+            .filter(|e| e.matches(year, day, input_hash, version))
+        {
+            answers.set(e.part, &e.answer);
+        }
+        answers
+    }
+
+    pub fn store(
+        &mut self,
+        year: usize,
+        day: usize,
+        input_hash: u64,
+        version: u32,
+        answers: &Answers,
+    ) -> io::Result<()> {
+        for (part, value) in answers.parts() {
+            self.entries
+                .retain(|e| !(e.part == part && e.matches(year, day, input_hash, version)));
+            self.entries.push(CacheEntry {
+                year,
+                day,
+                part,
+                input_hash,
+                version,
+                answer: value.replace(['\t', '\n'], " "),
+            });
+        }
+        self.save()
+    }
+
+    /// Removes every entry, or only those for one day. Returns how many were removed.
+    pub fn clear(&mut self, day: Option<usize>) -> io::Result<usize> {
+        let num_before = self.entries.len();
+        self.entries.retain(|e| day.is_some_and(|d| e.day != d));
+        let num_removed = num_before - self.entries.len();
+        self.save()?;
+        Ok(num_removed)
+    }
+
+    fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = File::create(&self.path)?;
+        for e in self.entries.iter() {
+            writeln!(file, "{}", e.to_line())?;
+        }
+        Ok(())
+    }
+}
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Hashes the input file together with the solver arguments that follow it,
+/// since some days (e.g. the day 11 expansion factor) answer differently per argument.
+/// Arguments naming a file (e.g. the day 1 `--words`) add its contents too.
+pub fn hash_input(path: &str, solver_args: &[String]) -> io::Result<u64> {
+    let mut hash = fnv1a(0xcbf29ce484222325, &fs::read(path)?);
+    for arg in solver_args {
+        hash = fnv1a(hash, &[0]);
+        hash = fnv1a(hash, arg.as_bytes());
+        if Path::new(arg).is_file() {
+            hash = fnv1a(hash, &fs::read(arg)?);
+        }
+    }
+    Ok(hash)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::test_dir::temp_dir;
+
+    #[test]
+    fn test_entry_round_trip() {
+        let entry = CacheEntry {
+            year: 2023,
+            day: 5,
+            part: 2,
+            input_hash: 0xdeadbeef,
+            version: 3,
+            answer: "46".to_owned(),
+        };
+        assert_eq!(CacheEntry::from_str(&entry.to_line()).unwrap(), entry);
+        assert!(CacheEntry::from_str("2023\t5\t2").is_err());
+    }
+
+    #[test]
+    fn test_store_and_lookup() {
+        let dir = temp_dir("cache-store");
+        let mut cache = AnswerCache::open(&dir).unwrap();
+        cache.store(2023, 5, 42, 1, &Answers::new(35, 46)).unwrap();
+
+        let cache = AnswerCache::open(&dir).unwrap();
+        assert_eq!(cache.lookup(2023, 5, 42, 1), Answers::new(35, 46));
+        assert!(cache.lookup(2023, 5, 43, 1).is_empty());
+        assert!(cache.lookup(2023, 5, 42, 2).is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_hash_arg_file_contents() {
+        let dir = temp_dir("cache-hash");
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.txt").display().to_string();
+        let words = dir.join("words.txt").display().to_string();
+        fs::write(&input, "eins2").unwrap();
+        fs::write(&words, "eins = 1").unwrap();
+        let args = ["--words".to_owned(), words.clone()];
+        let before = hash_input(&input, &args).unwrap();
+        assert_eq!(hash_input(&input, &args).unwrap(), before);
+        fs::write(&words, "eins = 7").unwrap();
+        assert_ne!(hash_input(&input, &args).unwrap(), before);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_clear_one_day() {
+        let dir = temp_dir("cache-clear");
+        let mut cache = AnswerCache::open(&dir).unwrap();
+        cache.store(2023, 5, 42, 1, &Answers::new(35, 46)).unwrap();
+        cache
+            .store(2023, 6, 42, 1, &Answers::new(288, 71503))
+            .unwrap();
+        assert_eq!(cache.clear(Some(5)).unwrap(), 2);
+        assert_eq!(cache.entries().len(), 2);
+        assert_eq!(cache.clear(None).unwrap(), 2);
+        assert!(AnswerCache::open(&dir).unwrap().entries().is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,75 @@
+use std::panic;
+
+/// The answers a day's solver produced for one input.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Answers {
+    pub part_1: Option<String>,
+    pub part_2: Option<String>,
+    /// Problems found in the input that didn't stop the solver.
+    pub diagnostics: Vec<String>,
+}
+
+impl Answers {
+    pub fn new(part_1: impl ToString, part_2: impl ToString) -> Self {
+        Self {
+            part_1: Some(part_1.to_string()),
+            part_2: Some(part_2.to_string()),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, part: usize) -> Option<&str> {
+        match part {
+            1 => self.part_1.as_deref(),
+            2 => self.part_2.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, part: usize, value: impl ToString) {
+        match part {
+            1 => self.part_1 = Some(value.to_string()),
+            2 => self.part_2 = Some(value.to_string()),
+            _ => println!("WARNING: No such part {part}"),
+        }
+    }
+
+    /// The solved parts, in order.
+    pub fn parts(&self) -> Vec<(usize, &str)> {
+        [1, 2]
+            .iter()
+            .filter_map(|&p| self.get(p).map(|v| (p, v)))
+            .collect()
+    }
+
+    pub fn warn(&mut self, msg: impl ToString) {
+        self.diagnostics.push(msg.to_string());
+    }
+
+    pub fn has_parts(&self, parts: &[usize]) -> bool {
+        parts.iter().all(|p| self.get(*p).is_some())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.part_1.is_none() && self.part_2.is_none()
+    }
+}
+
+/// Runs a solver, turning a panic (usually a parse failure) into a diagnostic.
+pub fn solve_catching_panics(func: fn(&[String]) -> Answers, args: &[String]) -> Answers {
+    match panic::catch_unwind(|| func(args)) {
+        Ok(answers) => answers,
+        Err(payload) => {
+            let msg = if let Some(s) = payload.downcast_ref::<&str>() {
+                s.to_string()
+            } else if let Some(s) = payload.downcast_ref::<String>() {
+                s.clone()
+            } else {
+                "unknown panic".to_owned()
+            };
+            let mut answers = Answers::default();
+            answers.warn(format!("Solver panicked: {msg}"));
+            answers
+        }
+    }
+}
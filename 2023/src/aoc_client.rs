@@ -0,0 +1,118 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::answer_cache::AnswerCache;
+
+const DEFAULT_BASE_URL: &str = "https://adventofcode.com";
+const USER_AGENT: &str = "github.com/MattMarti/advent-of-code-solutions";
+const DEFAULT_MIN_INTERVAL_MS: u64 = 5000;
+const LAST_REQUEST_FILE_NAME: &str = "last_request";
+
+/// Talks to the Advent of Code site, or anything pretending to be it.
+pub struct AocClient {
+    pub base_url: String,
+    session: String,
+    /// Where the time of the last request is kept, so the rate limit holds across runs
+    state_dir: PathBuf,
+    min_interval: Duration,
+}
+
+impl AocClient {
+    pub fn new(base_url: &str, session: &str, state_dir: PathBuf, min_interval: Duration) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            session: session.to_owned(),
+            state_dir,
+            min_interval,
+        }
+    }
+
+    /// Configures the client from `AOC_SESSION`, `AOC_BASE_URL` and
+    /// `AOC_MIN_INTERVAL_MS`. A `--base-url` argument wins over the environment.
+    pub fn from_env(args: &[String]) -> Result<Self, String> {
+        let session =
+            env::var("AOC_SESSION").map_err(|_| "Set AOC_SESSION to your session cookie")?;
+        let base_url = match args.iter().position(|a| a == "--base-url") {
+            Some(i) => args
+                .get(i + 1)
+                .cloned()
+                .ok_or("Missing value for --base-url")?,
+            None => env::var("AOC_BASE_URL").unwrap_or_else(|_| DEFAULT_BASE_URL.to_owned()),
+        };
+        let min_interval_ms = env::var("AOC_MIN_INTERVAL_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MIN_INTERVAL_MS);
+        Ok(Self::new(
+            &base_url,
+            &session,
+            AnswerCache::default_dir(),
+            Duration::from_millis(min_interval_ms),
+        ))
+    }
+
+    fn now_ms() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+
+    fn wait_for_rate_limit(&self) {
+        let path = self.state_dir.join(LAST_REQUEST_FILE_NAME);
+        let last_ms: u64 = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(0);
+        let next_ms = last_ms + self.min_interval.as_millis() as u64;
+        let now_ms = Self::now_ms();
+        if now_ms < next_ms {
+            println!("Rate limited, waiting {} ms", next_ms - now_ms);
+            sleep(Duration::from_millis(next_ms - now_ms));
+        }
+        let _ = fs::create_dir_all(&self.state_dir);
+        if let Err(e) = fs::write(&path, Self::now_ms().to_string()) {
+            println!("WARNING: Failed to record request time: {e}");
+        }
+    }
+
+    fn call(
+        &self,
+        request: ureq::Request,
+        form: Option<&[(&str, &str)]>,
+    ) -> Result<String, String> {
+        self.wait_for_rate_limit();
+        let request = request
+            .timeout(Duration::from_secs(30))
+            .set("Cookie", &format!("session={}", self.session))
+            .set("User-Agent", USER_AGENT);
+        let result = match form {
+            Some(fields) => request.send_form(fields),
+            None => request.call(),
+        };
+        match result {
+            Ok(response) => response
+                .into_string()
+                .map_err(|e| format!("Failed to read response: {e}")),
+            Err(ureq::Error::Status(code, response)) => Err(format!(
+                "Server returned {code}: {}",
+                response.into_string().unwrap_or_default().trim()
+            )),
+            Err(e) => Err(format!("Request failed: {e}")),
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Result<String, String> {
+        self.call(ureq::get(&format!("{}{}", self.base_url, path)), None)
+    }
+
+    pub fn post_form(&self, path: &str, fields: &[(&str, &str)]) -> Result<String, String> {
+        self.call(
+            ureq::post(&format!("{}{}", self.base_url, path)),
+            Some(fields),
+        )
+    }
+}
@@ -1,57 +1,492 @@
-use crate::load_file_lines;
-
-fn get_digits(lines: &[String], check_words: bool) -> Vec<i64> {
-    let take_values = if check_words { 2 } else { 1 };
-    let all_patterns: Vec<Vec<String>> = vec![
-        vec!["0".to_owned(), "zero".to_owned()],
-        vec!["1".to_owned(), "one".to_owned()],
-        vec!["2".to_owned(), "two".to_owned()],
-        vec!["3".to_owned(), "three".to_owned()],
-        vec!["4".to_owned(), "four".to_owned()],
-        vec!["5".to_owned(), "five".to_owned()],
-        vec!["6".to_owned(), "six".to_owned()],
-        vec!["7".to_owned(), "seven".to_owned()],
-        vec!["8".to_owned(), "eight".to_owned()],
-        vec!["9".to_owned(), "nine".to_owned()],
-    ];
-    let mut first_last_digits = Vec::<_>::new();
-    for line in lines {
-        println!("LINE: {line}");
-        let mut digits = Vec::<i32>::new();
-        for i in 0..line.len() {
-            for (d, patterns) in all_patterns.iter().enumerate() {
-                let mut match_found = false;
-                for p in patterns.iter().take(take_values) {
-                    if i + p.len() > line.len() {
-                        continue;
+use std::collections::HashMap;
+
+use crate::answers::Answers;
+use crate::{arg_value, load_file_lines};
+
+extern crate colored;
+use colored::*;
+
+pub const VERSION: u32 = 1;
+
+const ENGLISH_WORDS: [&str; 10] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+];
+
+/// The words that count as digits, with the digit each stands for.
+struct Vocabulary {
+    words: Vec<(String, u32)>,
+}
+
+impl Vocabulary {
+    pub fn digits() -> Self {
+        Self {
+            words: (0..10).map(|d| (d.to_string(), d)).collect(),
+        }
+    }
+
+    pub fn english() -> Self {
+        let mut vocabulary = Self::digits();
+        vocabulary.add_words(
+            ENGLISH_WORDS
+                .iter()
+                .zip(0..)
+                .map(|(w, d)| (w.to_string(), d)),
+        );
+        vocabulary
+    }
+
+    /// The digits plus a `word = digit` line per word. Blank lines and lines
+    /// starting with `#` are skipped.
+    pub fn parse_config(text: &str) -> Result<Self, String> {
+        let mut words = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: String| format!("Line {}: {msg}", i + 1);
+            let Some((word, digit)) = line.split_once('=') else {
+                return Err(err(format!("Expected word = digit, got {line}")));
+            };
+            let word = word.trim();
+            if word.is_empty() {
+                return Err(err("The word is empty".to_owned()));
+            }
+            let digit = digit
+                .trim()
+                .parse()
+                .ok()
+                .filter(|d| *d < 10)
+                .ok_or_else(|| err(format!("Bad digit {}", digit.trim())))?;
+            words.push((word.to_owned(), digit));
+        }
+        let mut vocabulary = Self::digits();
+        vocabulary.add_words(words);
+        Ok(vocabulary)
+    }
+
+    fn add_words(&mut self, words: impl IntoIterator<Item = (String, u32)>) {
+        self.words.extend(words);
+    }
+}
+
+/// A word found in a line, from byte `start` up to `end`.
+#[derive(Debug, PartialEq)]
+struct Token {
+    start: usize,
+    end: usize,
+    digit: u32,
+}
+
+/// An Aho-Corasick automaton over the vocabulary's bytes, which finds every
+/// word in a line in one pass, overlapping ones included.
+struct Scanner {
+    transitions: Vec<HashMap<u8, usize>>,
+    fallback: Vec<usize>,
+    // The (length, digit) of each word ending at a state
+    matches: Vec<Vec<(usize, u32)>>,
+}
+
+impl Scanner {
+    pub fn new(vocabulary: &Vocabulary) -> Self {
+        let mut scanner = Self {
+            transitions: vec![HashMap::new()],
+            fallback: vec![0],
+            matches: vec![Vec::new()],
+        };
+        for (word, digit) in vocabulary.words.iter() {
+            let mut state = 0;
+            for b in word.bytes() {
+                state = match scanner.transitions[state].get(&b) {
+                    Some(next) => *next,
+                    None => {
+                        scanner.transitions.push(HashMap::new());
+                        scanner.fallback.push(0);
+                        scanner.matches.push(Vec::new());
+                        let next = scanner.transitions.len() - 1;
+                        scanner.transitions[state].insert(b, next);
+                        next
                     }
-                    let substr = &line[i..i + p.len()];
-                    if substr == p {
-                        digits.push(d as i32);
-                        match_found = true;
-                        break;
+                };
+            }
+            scanner.matches[state].push((word.len(), *digit));
+        }
+
+        // Breadth first, so each state's fallback is done before its children
+        let mut queue: Vec<usize> = scanner.transitions[0].values().copied().collect();
+        let mut i = 0;
+        while i < queue.len() {
+            let state = queue[i];
+            i += 1;
+            let children: Vec<(u8, usize)> = scanner.transitions[state]
+                .iter()
+                .map(|(b, s)| (*b, *s))
+                .collect();
+            for (b, child) in children {
+                let fallback = scanner.follow(scanner.fallback[state], b);
+                scanner.fallback[child] = fallback;
+                let inherited = scanner.matches[fallback].clone();
+                scanner.matches[child].extend(inherited);
+                queue.push(child);
+            }
+        }
+        scanner
+    }
+
+    fn follow(&self, mut state: usize, b: u8) -> usize {
+        loop {
+            if let Some(next) = self.transitions[state].get(&b) {
+                return *next;
+            }
+            if state == 0 {
+                return 0;
+            }
+            state = self.fallback[state];
+        }
+    }
+
+    /// Every word in the line, in the order they start.
+    pub fn scan(&self, line: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut state = 0;
+        for (i, b) in line.bytes().enumerate() {
+            state = self.follow(state, b);
+            for (len, digit) in self.matches[state].iter() {
+                tokens.push(Token {
+                    start: i + 1 - len,
+                    end: i + 1,
+                    digit: *digit,
+                });
+            }
+        }
+        tokens.sort_by_key(|t| (t.start, t.end));
+        tokens
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Highlight {
+    Plain,
+    Chosen,
+    Other,
+}
+
+/// What was found on one line of the calibration document.
+#[derive(Debug, PartialEq)]
+struct LineReport {
+    line_number: usize,
+    tokens: Vec<Token>,
+    first_last: Option<(u32, u32)>,
+    value: Option<u32>,
+}
+
+impl LineReport {
+    pub fn new(line_number: usize, tokens: Vec<Token>) -> Self {
+        let first_last = match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => Some((first.digit, last.digit)),
+            _ => None,
+        };
+        Self {
+            line_number,
+            tokens,
+            first_last,
+            value: first_last.map(|(first, last)| 10 * first + last),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let tokens: Vec<String> = self
+            .tokens
+            .iter()
+            .map(|t| format!("{}@{}..{}", t.digit, t.start, t.end))
+            .collect();
+        match (self.first_last, self.value) {
+            (Some((first, last)), Some(value)) => format!(
+                "Line {}: [{}] first {first}, last {last}, value {value}",
+                self.line_number,
+                tokens.join(", ")
+            ),
+            _ => format!("Line {}: no digits", self.line_number),
+        }
+    }
+
+    /// Splits the line into runs of unmatched text, the first and last words,
+    /// and any other words.
+    pub fn spans<'l>(&self, line: &'l str) -> Vec<(Highlight, &'l str)> {
+        let chosen = [self.tokens.first(), self.tokens.last()];
+        let mut spans: Vec<(Highlight, &str)> = Vec::new();
+        let mut start = 0;
+        for (i, _) in line.char_indices() {
+            let within = |t: &Token| t.start <= i && i < t.end;
+            let highlight = if chosen.iter().flatten().any(|t| within(t)) {
+                Highlight::Chosen
+            } else if self.tokens.iter().any(within) {
+                Highlight::Other
+            } else {
+                Highlight::Plain
+            };
+            match spans.last() {
+                Some((last, _)) if *last == highlight => (),
+                _ => {
+                    if let Some(last) = spans.last_mut() {
+                        last.1 = &line[start..i];
                     }
-                }
-                if match_found {
-                    break;
+                    start = i;
+                    spans.push((highlight, ""));
                 }
             }
         }
-        if let (Some(first), Some(last)) = (digits.first(), digits.last()) {
-            println!("- [{first}, {last}]");
-            first_last_digits.push((10 * first + last) as i64);
-        } else {
-            println!("WARNING: No digits found in {}", line);
+        if let Some(last) = spans.last_mut() {
+            last.1 = &line[start..];
+        }
+        spans
+    }
+
+    /// The line with the first and last words in green and any others in
+    /// yellow.
+    pub fn highlight(&self, line: &str) -> String {
+        let mut out = String::new();
+        for (highlight, text) in self.spans(line) {
+            out += &match highlight {
+                Highlight::Chosen => text.green().bold().to_string(),
+                Highlight::Other => text.yellow().to_string(),
+                Highlight::Plain => text.to_owned(),
+            };
+        }
+        if let Some(value) = self.value {
+            out += &format!(" = {value}");
         }
+        out
+    }
+}
+
+/// Which part a run answers, if its args only pick the part and the words.
+pub fn cached_parts(args: &[String]) -> Option<Vec<usize>> {
+    let args = match args.iter().position(|a| a == "--words") {
+        Some(i) if i + 2 == args.len() => &args[..i],
+        Some(_) => return None,
+        None => args,
+    };
+    match args {
+        [] => Some(vec![1]),
+        [part] if part == "part_1" => Some(vec![1]),
+        [part] if part == "part_2" => Some(vec![2]),
+        _ => None,
     }
-    first_last_digits
 }
 
-pub fn run(args: &[String]) {
+pub fn run(args: &[String]) -> Answers {
     let lines = load_file_lines(&args[0]).unwrap();
 
     let check_words = args.len() >= 2 && args[1] == "part_2";
+    let debug = args.contains(&"debug".to_owned());
+    let color = args.contains(&"color".to_owned());
+    let words_file = arg_value(args, "--words");
+
+    let vocabulary = match (check_words, words_file) {
+        (false, _) => Ok(Vocabulary::digits()),
+        (true, None) => Ok(Vocabulary::english()),
+        (true, Some(path)) => std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {path}: {e}"))
+            .and_then(|text| Vocabulary::parse_config(&text)),
+    };
+    let vocabulary = match vocabulary {
+        Ok(vocabulary) => vocabulary,
+        Err(msg) => {
+            println!("WARNING: {msg}");
+            let mut answers = Answers::default();
+            answers.warn(msg);
+            return answers;
+        }
+    };
+
+    let scanner = Scanner::new(&vocabulary);
+    let reports: Vec<LineReport> = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| LineReport::new(i + 1, scanner.scan(line)))
+        .collect();
+
+    let mut answers = Answers::default();
+    if let (false, Some(path)) = (check_words, words_file) {
+        answers.warn(format!(
+            "Ignoring --words {path}, words only count in part_2"
+        ));
+    }
+    for (report, line) in reports.iter().zip(lines.iter()) {
+        if debug {
+            println!("{}", report.describe());
+        }
+        if color {
+            println!("{}", report.highlight(line));
+        }
+        if report.value.is_none() {
+            answers.warn(format!(
+                "Line {}: No digits found in {line}",
+                report.line_number
+            ));
+        }
+    }
+    let sum = reports.iter().filter_map(|r| r.value).sum::<u32>();
+    println!("Sum of digits: {}", sum);
+
+    answers.set(if check_words { 2 } else { 1 }, sum);
+    for msg in answers.diagnostics.iter() {
+        println!("WARNING: {msg}");
+    }
+    answers
+}
 
-    let digits = get_digits(&lines, check_words);
-    println!("Sum of digits: {}", digits.iter().sum::<i64>());
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::test_dir::temp_dir;
+    use rstest::rstest;
+    use std::fs;
+
+    fn first_last(scanner: &Scanner, line: &str) -> Option<(u32, u32)> {
+        let tokens = scanner.scan(line);
+        Some((tokens.first()?.digit, tokens.last()?.digit))
+    }
+
+    #[rstest]
+    #[case("1abc2", Some((1, 2)))]
+    #[case("treb7uchet", Some((7, 7)))]
+    #[case("two1nine", Some((1, 1)))]
+    #[case("eightwo", None)]
+    fn test_digits_only(#[case] line: &str, #[case] expected: Option<(u32, u32)>) {
+        let scanner = Scanner::new(&Vocabulary::digits());
+        assert_eq!(first_last(&scanner, line), expected);
+    }
+
+    #[rstest]
+    #[case("two1nine", Some((2, 9)))]
+    #[case("eightwothree", Some((8, 3)))]
+    #[case("abcone2threexyz", Some((1, 3)))]
+    #[case("xtwone3four", Some((2, 4)))]
+    #[case("4nineeightseven2", Some((4, 2)))]
+    #[case("zoneight234", Some((1, 4)))]
+    #[case("7pqrstsixteen", Some((7, 6)))]
+    #[case("eightwo", Some((8, 2)))]
+    #[case("oneight", Some((1, 8)))]
+    #[case("sevenine", Some((7, 9)))]
+    #[case("nothing", None)]
+    fn test_words(#[case] line: &str, #[case] expected: Option<(u32, u32)>) {
+        let scanner = Scanner::new(&Vocabulary::english());
+        assert_eq!(first_last(&scanner, line), expected);
+    }
+
+    #[test]
+    fn test_overlapping_tokens() {
+        let scanner = Scanner::new(&Vocabulary::english());
+        let tokens = scanner.scan("twoneight");
+        assert_eq!(
+            tokens,
+            vec![
+                Token {
+                    start: 0,
+                    end: 3,
+                    digit: 2
+                },
+                Token {
+                    start: 2,
+                    end: 5,
+                    digit: 1
+                },
+                Token {
+                    start: 4,
+                    end: 9,
+                    digit: 8
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_vocabulary() {
+        let vocabulary = Vocabulary::parse_config(
+            "# German\neins = 1\nzwei = 2\ndrei = 3\nsieben = 7\nneun = 9\n",
+        )
+        .unwrap();
+        let scanner = Scanner::new(&vocabulary);
+        assert_eq!(first_last(&scanner, "zweins"), Some((2, 1)));
+        assert_eq!(first_last(&scanner, "xsiebeneun"), Some((7, 9)));
+        assert_eq!(first_last(&scanner, "4dreione"), Some((4, 3)));
+    }
+
+    #[rstest]
+    #[case("eins 1")]
+    #[case("eins = 10")]
+    #[case(" = 1")]
+    fn test_vocabulary_errors(#[case] text: &str) {
+        assert!(Vocabulary::parse_config(text).is_err());
+    }
+
+    #[test]
+    fn test_line_report() {
+        let scanner = Scanner::new(&Vocabulary::english());
+        let report = LineReport::new(3, scanner.scan("xoneight5"));
+        assert_eq!(report.first_last, Some((1, 5)));
+        assert_eq!(report.value, Some(15));
+        assert_eq!(
+            report.describe(),
+            "Line 3: [1@1..4, 8@3..8, 5@8..9] first 1, last 5, value 15"
+        );
+
+        let report = LineReport::new(4, scanner.scan("xyz"));
+        assert_eq!(report.value, None);
+        assert_eq!(report.describe(), "Line 4: no digits");
+    }
+
+    #[test]
+    fn test_highlight_spans() {
+        let scanner = Scanner::new(&Vocabulary::english());
+        let line = "xoneight5";
+        let report = LineReport::new(1, scanner.scan(line));
+        assert_eq!(
+            report.spans(line),
+            vec![
+                (Highlight::Plain, "x"),
+                (Highlight::Chosen, "one"),
+                (Highlight::Other, "ight"),
+                (Highlight::Chosen, "5"),
+            ]
+        );
+        assert_eq!(
+            LineReport::new(2, vec![]).spans("abc"),
+            vec![(Highlight::Plain, "abc")]
+        );
+        assert!(LineReport::new(3, vec![]).spans("").is_empty());
+    }
+
+    #[rstest]
+    #[case(&[], Some(vec![1]))]
+    #[case(&["part_2"], Some(vec![2]))]
+    #[case(&["part_2", "--words", "words.txt"], Some(vec![2]))]
+    #[case(&["part_2", "color"], None)]
+    #[case(&["debug"], None)]
+    fn test_cached_parts(#[case] args: &[&str], #[case] expected: Option<Vec<usize>>) {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        assert_eq!(cached_parts(&args), expected);
+    }
+
+    #[test]
+    fn test_words_need_part_2() {
+        let dir = temp_dir("trebuchet-words");
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.txt").display().to_string();
+        let words = dir.join("words.txt").display().to_string();
+        fs::write(&input, "two1nine\n").unwrap();
+        fs::write(&words, "nine = 9\n").unwrap();
+
+        let answers = run(&[input.clone(), "--words".to_owned(), words.clone()]);
+        assert_eq!(answers.get(1), Some("11"));
+        assert_eq!(answers.diagnostics.len(), 1);
+        assert!(answers.diagnostics[0].contains("--words"));
+
+        let answers = run(&[input, "part_2".to_owned(), "--words".to_owned(), words]);
+        assert_eq!(answers.get(2), Some("19"));
+        assert!(answers.diagnostics.is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
@@ -2,8 +2,11 @@ use core::iter::zip;
 use lazy_static::lazy_static;
 use regex::Regex;
 
+use crate::answers::Answers;
 use crate::load_file_lines;
 
+pub const VERSION: u32 = 1;
+
 #[derive(Default, Debug)]
 struct CubeCounts {
     red: usize,
@@ -59,7 +62,7 @@ fn is_possible(candidate: &CubeCounts, actual: &CubeCounts) -> bool {
     actual.red <= candidate.red && actual.green <= candidate.green && actual.blue <= candidate.blue
 }
 
-pub fn run(args: &[String]) {
+pub fn run(args: &[String]) -> Answers {
     let game_results = load_game_results(&args[0]);
 
     let part_1_candidate = CubeCounts {
@@ -107,4 +110,6 @@ pub fn run(args: &[String]) {
         part_2_checksum += power;
     }
     println!("Part 2 checksum: {}", part_2_checksum);
+
+    Answers::new(part_1_checksum, part_2_checksum)
 }
@@ -1,5 +1,8 @@
+use crate::answers::Answers;
 use crate::load_file_lines;
 
+pub const VERSION: u32 = 1;
+
 fn is_special_symbol(c: char) -> bool {
     c != '.' && !c.is_numeric()
 }
@@ -137,14 +140,18 @@ fn find_gear_ratios(lines: &[String]) -> Vec<i64> {
     gear_ratios
 }
 
-pub fn run(args: &[String]) {
+pub fn run(args: &[String]) -> Answers {
     let lines = load_file_lines(&args[0]).unwrap();
 
     let verbose = args.contains(&String::from("debug"));
 
     let lonely_nums = find_non_adjacent_nums(&lines, verbose);
-    println!("Part 1 checksum: {}", lonely_nums.iter().sum::<i64>());
+    let part_1_checksum = lonely_nums.iter().sum::<i64>();
+    println!("Part 1 checksum: {}", part_1_checksum);
 
     let gear_ratios = find_gear_ratios(&lines);
-    println!("Part 2 checksum: {}", gear_ratios.iter().sum::<i64>());
+    let part_2_checksum = gear_ratios.iter().sum::<i64>();
+    println!("Part 2 checksum: {}", part_2_checksum);
+
+    Answers::new(part_1_checksum, part_2_checksum)
 }
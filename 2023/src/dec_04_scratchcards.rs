@@ -1,5 +1,8 @@
+use crate::answers::Answers;
 use crate::load_file_lines;
 
+pub const VERSION: u32 = 1;
+
 extern crate colored;
 use colored::*;
 
@@ -42,7 +45,7 @@ fn print_first_part(line: &str) -> Result<(), &'static str> {
     Ok(())
 }
 
-pub fn run(args: &[String]) {
+pub fn run(args: &[String]) -> Answers {
     let lines = load_file_lines(&args[0]).unwrap();
     let mut card_copy_counts: Vec<i32> = vec![0; lines.len()];
     let mut total_score = 0;
@@ -104,11 +107,11 @@ pub fn run(args: &[String]) {
             card_copy_counts[i]
         );
     }
+    let num_cards = card_copy_counts.iter().map(|n| n + 1).sum::<i32>();
     println!("Total score (part 1): {total_score}");
-    println!(
-        "Num cards (part 2): {}",
-        card_copy_counts.iter().map(|n| n + 1).sum::<i32>()
-    );
+    println!("Num cards (part 2): {}", num_cards);
+
+    Answers::new(total_score, num_cards)
 }
 
 #[cfg(test)]
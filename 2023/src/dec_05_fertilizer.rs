@@ -1,9 +1,13 @@
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt;
 
 use lazy_static::lazy_static;
 use regex::Regex;
 
-use crate::load_file_lines;
+use crate::answers::Answers;
+use crate::{arg_value, load_file_lines};
+
+pub const VERSION: u32 = 1;
 
 struct AlmanacRange {
     src: usize,
@@ -34,7 +38,7 @@ impl fmt::Debug for AlmanacRange {
 
 #[derive(Debug)]
 struct AlmanacMap {
-    //src_type: String, // TODO if maps aren't sorted
+    src_type: String,
     dest_type: String,
     ranges: Vec<AlmanacRange>,
 }
@@ -47,7 +51,7 @@ impl AlmanacMap {
         };
         if let Some(cap) = RE.captures(range_decl) {
             Some(Self {
-                //src_type: cap["src_type"].to_owned(), // TODO if maps aren't sorted
+                src_type: cap["src_type"].to_owned(),
                 dest_type: cap["dest_type"].to_owned(),
                 ranges: Vec::new(),
             })
@@ -65,6 +69,208 @@ impl AlmanacMap {
         }
         n
     }
+
+    /// Maps half-open `[start, end)` intervals a whole piece at a time,
+    /// splitting them where they cross range boundaries. Ranges are tried in
+    /// order, so this agrees with `transform` on every value.
+    pub fn transform_intervals(&self, intervals: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        let mut unmapped: Vec<(usize, usize)> = intervals
+            .iter()
+            .filter(|(start, end)| start < end)
+            .cloned()
+            .collect();
+        let mut mapped = Vec::with_capacity(unmapped.len());
+        for r in self.ranges.iter() {
+            let mut remaining = Vec::with_capacity(unmapped.len());
+            for (start, end) in unmapped {
+                let overlap_start = usize::max(start, r.src);
+                let overlap_end = usize::min(end, r.src + r.range);
+                if overlap_start >= overlap_end {
+                    remaining.push((start, end));
+                    continue;
+                }
+                mapped.push((overlap_start - r.src + r.dest, overlap_end - r.src + r.dest));
+                if start < overlap_start {
+                    remaining.push((start, overlap_start));
+                }
+                if overlap_end < end {
+                    remaining.push((overlap_end, end));
+                }
+            }
+            unmapped = remaining;
+        }
+        mapped.extend(unmapped);
+        mapped
+    }
+
+    fn name(&self) -> String {
+        format!("{}-to-{}", self.src_type, self.dest_type)
+    }
+
+    /// Disjoint `(src_start, src_end, dest_start)` pieces sorted by source,
+    /// with earlier ranges winning where ranges overlap like in `transform`.
+    fn pieces(&self) -> Vec<(usize, usize, usize)> {
+        let mut pieces: Vec<(usize, usize, usize)> = Vec::with_capacity(self.ranges.len());
+        for r in self.ranges.iter() {
+            let mut unclaimed = vec![(r.src, r.src + r.range)];
+            for (claimed_start, claimed_end, _) in pieces.iter() {
+                unclaimed = unclaimed
+                    .into_iter()
+                    .flat_map(|(start, end)| {
+                        [
+                            (start, usize::min(end, *claimed_start)),
+                            (usize::max(start, *claimed_end), end),
+                        ]
+                    })
+                    .filter(|(start, end)| start < end)
+                    .collect();
+            }
+            pieces.extend(
+                unclaimed
+                    .into_iter()
+                    .map(|(start, end)| (start, end, start - r.src + r.dest)),
+            );
+        }
+        pieces.sort();
+        pieces
+    }
+
+    /// One map that does the same as this map followed by `then`.
+    pub fn compose(&self, then: &AlmanacMap) -> Result<AlmanacMap, String> {
+        if self.dest_type != then.src_type {
+            return Err(format!("Can't follow {} with {}", self.name(), then.name()));
+        }
+        let first = self.pieces();
+        let second = then.pieces();
+
+        // Both maps are linear between consecutive break points: the edges of
+        // the first map's pieces and wherever it sends values onto an edge
+        // of the second map's pieces
+        let mut breaks = vec![0];
+        for (start, end, _) in first.iter() {
+            breaks.extend([*start, *end]);
+        }
+        for edge in second.iter().flat_map(|(start, end, _)| [*start, *end]) {
+            breaks.push(edge);
+            for (start, end, dest) in first.iter() {
+                if *dest <= edge && edge < dest + (end - start) {
+                    breaks.push(start + (edge - dest));
+                }
+            }
+        }
+        breaks.sort_unstable();
+        breaks.dedup();
+
+        let mut ranges: Vec<AlmanacRange> = Vec::new();
+        for w in breaks.windows(2) {
+            let (src, range) = (w[0], w[1] - w[0]);
+            let dest = then.transform(self.transform(src));
+            if dest == src {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(last) if last.src + last.range == src && last.dest + last.range == dest => {
+                    last.range += range;
+                }
+                _ => ranges.push(AlmanacRange { src, dest, range }),
+            }
+        }
+        Ok(AlmanacMap {
+            src_type: self.src_type.clone(),
+            dest_type: then.dest_type.clone(),
+            ranges,
+        })
+    }
+
+    /// The map that undoes this one, if every value is reached exactly once.
+    pub fn invert(&self) -> Result<AlmanacMap, String> {
+        let pieces = self.pieces();
+        let mut images: Vec<(usize, usize)> = pieces
+            .iter()
+            .map(|(start, end, dest)| (*dest, dest + (end - start)))
+            .collect();
+        images.sort_unstable();
+        if let Some(w) = images.windows(2).find(|w| w[1].0 < w[0].1) {
+            return Err(format!(
+                "{} can't be inverted, it maps more than one value to {}",
+                self.name(),
+                w[1].0
+            ));
+        }
+        // Values outside the pieces map to themselves, so the pieces have to
+        // land exactly on their own sources
+        let covered = |intervals: Vec<(usize, usize)>| {
+            let mut merged: Vec<(usize, usize)> = Vec::new();
+            for (start, end) in intervals {
+                match merged.last_mut() {
+                    Some(last) if last.1 == start => last.1 = end,
+                    _ => merged.push((start, end)),
+                }
+            }
+            merged
+        };
+        let sources = covered(
+            pieces
+                .iter()
+                .map(|(start, end, _)| (*start, *end))
+                .collect(),
+        );
+        if covered(images) != sources {
+            return Err(format!(
+                "{} can't be inverted, some values are never reached",
+                self.name()
+            ));
+        }
+        Ok(AlmanacMap {
+            src_type: self.dest_type.clone(),
+            dest_type: self.src_type.clone(),
+            ranges: pieces
+                .iter()
+                .map(|(start, end, dest)| AlmanacRange {
+                    src: *dest,
+                    dest: *start,
+                    range: end - start,
+                })
+                .collect(),
+        })
+    }
+
+    /// Ranges whose sources overlap, where `transform` silently uses the
+    /// first, and gaps between ranges, which are left unmapped.
+    pub fn lint(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        for (i, a) in self.ranges.iter().enumerate() {
+            for (j, b) in self.ranges.iter().enumerate().skip(i + 1) {
+                let start = usize::max(a.src, b.src);
+                let end = usize::min(a.src + a.range, b.src + b.range);
+                if start < end {
+                    issues.push(format!(
+                        "{}: ranges {} and {} overlap on [{start}, {end})",
+                        self.name(),
+                        i + 1,
+                        j + 1
+                    ));
+                }
+            }
+        }
+        let mut sources: Vec<(usize, usize)> = self
+            .ranges
+            .iter()
+            .map(|r| (r.src, r.src + r.range))
+            .collect();
+        sources.sort_unstable();
+        let mut covered_to = None;
+        for (start, end) in sources {
+            if let Some(covered_end) = covered_to.filter(|e| *e < start) {
+                issues.push(format!(
+                    "{}: nothing maps [{covered_end}, {start})",
+                    self.name()
+                ));
+            }
+            covered_to = Some(covered_to.map_or(end, |e: usize| e.max(end)));
+        }
+        issues
+    }
 }
 
 fn parse_maps(lines: &[String]) -> Vec<AlmanacMap> {
@@ -93,6 +299,102 @@ fn parse_maps(lines: &[String]) -> Vec<AlmanacMap> {
     maps
 }
 
+/// Walks the category graph depth first from `category`, returning the
+/// categories around a cycle if it runs into one.
+fn find_cycle(
+    category: &str,
+    edges: &HashMap<&str, Vec<&str>>,
+    path: &mut Vec<String>,
+    done: &mut HashSet<String>,
+) -> Option<Vec<String>> {
+    if let Some(pos) = path.iter().position(|c| c == category) {
+        let mut cycle = path[pos..].to_vec();
+        cycle.push(category.to_owned());
+        return Some(cycle);
+    }
+    if done.contains(category) {
+        return None;
+    }
+    path.push(category.to_owned());
+    for next in edges.get(category).into_iter().flatten() {
+        if let Some(cycle) = find_cycle(next, edges, path, done) {
+            return Some(cycle);
+        }
+    }
+    path.pop();
+    done.insert(category.to_owned());
+    None
+}
+
+/// Finds the maps that convert `from` values into `to` values, in the order
+/// they have to be applied, wherever they appear in the file.
+fn resolve_chain<'m>(
+    maps: &'m [AlmanacMap],
+    from: &str,
+    to: &str,
+) -> Result<Vec<&'m AlmanacMap>, String> {
+    let mut edges: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (i, m) in maps.iter().enumerate() {
+        if maps[..i]
+            .iter()
+            .any(|other| other.src_type == m.src_type && other.dest_type == m.dest_type)
+        {
+            return Err(format!("Duplicate map {}-to-{}", m.src_type, m.dest_type));
+        }
+        edges.entry(&m.src_type).or_default().push(&m.dest_type);
+    }
+    let mut done = HashSet::new();
+    for m in maps.iter() {
+        if let Some(cycle) = find_cycle(&m.src_type, &edges, &mut Vec::new(), &mut done) {
+            return Err(format!("Maps form a cycle: {}", cycle.join(" -> ")));
+        }
+    }
+
+    // Breadth first, remembering which map reached each category
+    let mut reached_by: HashMap<&str, Option<&AlmanacMap>> = HashMap::from([(from, None)]);
+    let mut queue = VecDeque::from([from]);
+    while let Some(category) = queue.pop_front() {
+        for m in maps.iter().filter(|m| m.src_type == category) {
+            if !reached_by.contains_key(m.dest_type.as_str()) {
+                reached_by.insert(&m.dest_type, Some(m));
+                queue.push_back(&m.dest_type);
+            }
+        }
+    }
+    if !reached_by.contains_key(to) {
+        let mut reachable: Vec<&str> = reached_by.keys().filter(|c| **c != from).cloned().collect();
+        reachable.sort();
+        return Err(format!(
+            "No maps lead from {from} to {to}, {from} only reaches [{}]",
+            reachable.join(", ")
+        ));
+    }
+    let mut chain = Vec::new();
+    let mut category = to;
+    while let Some(Some(m)) = reached_by.get(category) {
+        chain.push(*m);
+        category = &m.src_type;
+    }
+    chain.reverse();
+    Ok(chain)
+}
+
+/// Collapses a chain of maps into one map, None if the chain is empty.
+fn compose_chain(chain: &[&AlmanacMap]) -> Result<Option<AlmanacMap>, String> {
+    let Some(first) = chain.first() else {
+        return Ok(None);
+    };
+    let mut composed = AlmanacMap {
+        src_type: first.src_type.clone(),
+        dest_type: first.src_type.clone(),
+        ranges: Vec::new(),
+    };
+    for m in chain.iter() {
+        composed = composed.compose(m)?;
+    }
+    Ok(Some(composed))
+}
+
 fn parse_seeds(line: &str) -> Vec<usize> {
     let start_idx = line.chars().position(|c| c == ':').unwrap() + 1;
     line.chars()
@@ -103,10 +405,50 @@ fn parse_seeds(line: &str) -> Vec<usize> {
         .collect()
 }
 
-pub fn run(args: &[String]) {
+/// Part 2 reads the seeds as `(start, length)` pairs.
+fn seed_intervals(seeds: &[usize]) -> Vec<(usize, usize)> {
+    seeds
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[0] + pair[1]))
+        .collect()
+}
+
+fn lowest_location(seed_intervals: &[(usize, usize)], chain: &[&AlmanacMap]) -> Option<usize> {
+    let mut intervals = seed_intervals.to_vec();
+    for m in chain.iter() {
+        intervals = m.transform_intervals(&intervals);
+    }
+    intervals.iter().map(|(start, _)| *start).min()
+}
+
+/// Sends every seed through the maps one at a time. Far too slow for real
+/// inputs, but there to check `lowest_location` against.
+fn lowest_location_per_seed(
+    seed_intervals: &[(usize, usize)],
+    chain: &[&AlmanacMap],
+) -> Option<usize> {
+    let mut min_loc = None;
+    for (start, end) in seed_intervals.iter() {
+        println!("Checking range [{}, {})", start, end);
+        for mut value in *start..*end {
+            for m in chain.iter() {
+                value = m.transform(value);
+            }
+            min_loc = Some(min_loc.map_or(value, |m| usize::min(m, value)));
+        }
+    }
+    min_loc
+}
+
+pub fn run(args: &[String]) -> Answers {
     let verbose = args.contains(&String::from("debug"));
+    let verify = args.contains(&String::from("verify"));
+    let lint = args.contains(&String::from("lint"));
     let lines = load_file_lines(&args[0]).unwrap();
 
+    let from = arg_value(args, "--from").unwrap_or("seed");
+    let to = arg_value(args, "--to").unwrap_or("location");
+
     let seeds = parse_seeds(&lines[0]);
     let maps = parse_maps(&lines[2..]);
     if verbose {
@@ -114,48 +456,259 @@ pub fn run(args: &[String]) {
             println!("{:?}", m);
         }
     }
+    let chain = match resolve_chain(&maps, from, to) {
+        Ok(chain) => chain,
+        Err(msg) => {
+            println!("WARNING: {msg}");
+            let mut answers = Answers::default();
+            answers.warn(msg);
+            return answers;
+        }
+    };
+
+    let mut answers = Answers::default();
+    if lint {
+        for m in maps.iter() {
+            m.lint().into_iter().for_each(|msg| answers.warn(msg));
+        }
+    }
 
-    println!("Seeds:");
+    println!("Seeds ({from}):");
     println!("{:?}", seeds);
     let mut values = seeds.clone();
-    for m in maps.iter() {
-        // TODO Assuming map traversal is in order
+    for m in chain.iter() {
         println!("{}:", m.dest_type);
         values.iter_mut().for_each(|v| *v = m.transform(*v));
         println!("{:?}", values);
     }
-    println!("Lowest location (part 1): {}", values.iter().min().unwrap());
+    let min_seed_loc = *values.iter().min().unwrap();
+    println!("Lowest {to} (part 1): {}", min_seed_loc);
 
     println!();
-    println!("Starting part 2...");
-    let mut min_loc = usize::MAX;
-    for (start, range) in seeds
-        .iter()
-        .take(seeds.len() - 1)
-        .step_by(2)
-        .zip(seeds.iter().skip(1).step_by(2))
-    {
-        println!("Checking range [{}, {}]", *start, *start + *range);
-        for mut value in *start..*start + *range {
-            for m in maps.iter() {
-                // TODO Assuming map traversal is in order
-                value = m.transform(value);
+    let intervals = seed_intervals(&seeds);
+    answers.set(1, min_seed_loc);
+    match lowest_location(&intervals, &chain) {
+        Some(min_loc) => {
+            println!("Lowest {to} (part 2): {}", min_loc);
+            answers.set(2, min_loc);
+            if verify {
+                println!("Verifying part 2 one seed at a time...");
+                let per_seed = lowest_location_per_seed(&intervals, &chain);
+                if per_seed != Some(min_loc) {
+                    answers.warn(format!("Checking each seed gave {:?} instead", per_seed));
+                }
+            }
+            // Work backwards through the whole chain as one map
+            match compose_chain(&chain).and_then(|m| m.map(|m| m.invert()).transpose()) {
+                Ok(Some(inverse)) => {
+                    if verbose {
+                        println!("{:?}", inverse);
+                    }
+                    println!("{from} for the lowest {to}: {}", inverse.transform(min_loc));
+                }
+                Ok(None) => {}
+                Err(msg) => println!("{msg}"),
             }
-            min_loc = usize::min(value, min_loc);
         }
+        None => answers.warn("No seed ranges for part 2"),
     }
-    println!("Lowest location (part 2): {}", min_loc);
+    for msg in answers.diagnostics.iter() {
+        println!("WARNING: {msg}");
+    }
+    answers
 }
 
 #[cfg(test)]
 pub mod test {
     use super::*;
 
+    fn example_lines() -> Vec<String> {
+        [
+            "seeds: 79 14 55 13",
+            "",
+            "seed-to-soil map:",
+            "50 98 2",
+            "52 50 48",
+            "",
+            "soil-to-fertilizer map:",
+            "0 15 37",
+            "37 52 2",
+            "39 0 15",
+            "",
+            "fertilizer-to-water map:",
+            "49 53 8",
+            "0 11 42",
+            "42 0 7",
+            "57 7 4",
+            "",
+            "water-to-light map:",
+            "88 18 7",
+            "18 25 70",
+            "",
+            "light-to-temperature map:",
+            "45 77 23",
+            "81 45 19",
+            "68 64 13",
+            "",
+            "temperature-to-humidity map:",
+            "0 69 1",
+            "1 0 69",
+            "",
+            "humidity-to-location map:",
+            "60 56 37",
+            "56 93 4",
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect()
+    }
+
+    #[test]
+    fn test_lowest_location_by_interval() {
+        let lines = example_lines();
+        let seeds = parse_seeds(&lines[0]);
+        let maps = parse_maps(&lines[2..]);
+        let chain = resolve_chain(&maps, "seed", "location").unwrap();
+        let intervals = seed_intervals(&seeds);
+        assert_eq!(intervals, vec![(79, 93), (55, 68)]);
+        assert_eq!(lowest_location(&intervals, &chain), Some(46));
+        assert_eq!(lowest_location_per_seed(&intervals, &chain), Some(46));
+    }
+
+    fn chain_types(chain: &[&AlmanacMap]) -> Vec<String> {
+        chain
+            .iter()
+            .map(|m| format!("{}-{}", m.src_type, m.dest_type))
+            .collect()
+    }
+
+    #[test]
+    fn test_chain_in_any_order() {
+        let lines = example_lines();
+        let mut maps = parse_maps(&lines[2..]);
+        maps.reverse();
+        let chain = resolve_chain(&maps, "soil", "light").unwrap();
+        assert_eq!(
+            chain_types(&chain),
+            vec!["soil-fertilizer", "fertilizer-water", "water-light"]
+        );
+        let chain = resolve_chain(&maps, "seed", "location").unwrap();
+        assert_eq!(chain.len(), 7);
+        let intervals = seed_intervals(&parse_seeds(&lines[0]));
+        assert_eq!(lowest_location(&intervals, &chain), Some(46));
+        assert!(resolve_chain(&maps, "seed", "seed").unwrap().is_empty());
+    }
+
+    fn map(src_type: &str, dest_type: &str) -> AlmanacMap {
+        AlmanacMap::from_decl(&format!("{src_type}-to-{dest_type} map:")).unwrap()
+    }
+
+    #[test]
+    fn test_chain_errors() {
+        let maps = vec![map("seed", "soil"), map("soil", "water")];
+        assert_eq!(
+            resolve_chain(&maps, "seed", "light").unwrap_err(),
+            "No maps lead from seed to light, seed only reaches [soil, water]"
+        );
+        assert!(resolve_chain(&maps, "water", "seed").is_err());
+
+        let maps = vec![
+            map("seed", "soil"),
+            map("soil", "water"),
+            map("seed", "soil"),
+        ];
+        assert_eq!(
+            resolve_chain(&maps, "seed", "water").unwrap_err(),
+            "Duplicate map seed-to-soil"
+        );
+
+        let maps = vec![
+            map("seed", "soil"),
+            map("water", "soil"),
+            map("soil", "light"),
+            map("light", "water"),
+        ];
+        assert_eq!(
+            resolve_chain(&maps, "seed", "light").unwrap_err(),
+            "Maps form a cycle: soil -> light -> water -> soil"
+        );
+    }
+
+    #[test]
+    fn test_compose_and_invert() {
+        let lines = example_lines();
+        let maps = parse_maps(&lines[2..]);
+        let chain = resolve_chain(&maps, "seed", "location").unwrap();
+        let composed = compose_chain(&chain).unwrap().unwrap();
+        assert_eq!(composed.src_type, "seed");
+        assert_eq!(composed.dest_type, "location");
+        assert!(composed.lint().is_empty());
+        let inverse = composed.invert().unwrap();
+        for seed in 0..150 {
+            let location = chain.iter().fold(seed, |v, m| m.transform(v));
+            assert_eq!(composed.transform(seed), location);
+            assert_eq!(inverse.transform(location), seed);
+        }
+        assert_eq!(inverse.transform(46), 82);
+        assert!(maps[1].compose(&maps[0]).is_err());
+    }
+
+    fn map_with(ranges: &[&str]) -> AlmanacMap {
+        let mut m = map("a", "b");
+        m.ranges = ranges.iter().map(|r| AlmanacRange::from_str(r)).collect();
+        m
+    }
+
+    #[test]
+    fn test_not_invertible() {
+        // 10 and 20 both end up at 20
+        assert!(map_with(&["20 10 1"]).invert().is_err());
+        assert!(map_with(&["30 10 5", "10 30 5"]).invert().is_ok());
+        // Nothing reaches 5..8
+        assert!(map_with(&["0 5 3", "5 0 2"]).invert().is_err());
+    }
+
+    #[test]
+    fn test_lint() {
+        let m = map_with(&["50 98 2", "52 50 48", "0 60 5", "0 200 10"]);
+        assert_eq!(
+            m.lint(),
+            vec![
+                "a-to-b: ranges 2 and 3 overlap on [60, 65)",
+                "a-to-b: nothing maps [100, 200)",
+            ]
+        );
+        // First match wins, like transform
+        assert_eq!(m.compose(&map("b", "c")).unwrap().transform(62), 64);
+    }
+
+    #[test]
+    fn test_transform_intervals_matches_transform() {
+        let lines = example_lines();
+        let maps = parse_maps(&lines[2..]);
+        for m in maps.iter() {
+            for start in 0..110 {
+                for len in [1, 3, 17] {
+                    let mut by_interval: Vec<usize> = m
+                        .transform_intervals(&[(start, start + len)])
+                        .iter()
+                        .flat_map(|(s, e)| *s..*e)
+                        .collect();
+                    let mut by_value: Vec<usize> =
+                        (start..start + len).map(|n| m.transform(n)).collect();
+                    by_interval.sort();
+                    by_value.sort();
+                    assert_eq!(by_interval, by_value);
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_map_delcaration() {
         let line = "source-to-dest map:";
         let map = AlmanacMap::from_decl(&line).unwrap();
-        //assert_eq!(map.src_type, "source");
+        assert_eq!(map.src_type, "source");
         assert_eq!(map.dest_type, "dest");
     }
 }
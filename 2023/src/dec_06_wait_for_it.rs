@@ -1,6 +1,9 @@
-use crate::load_file_lines;
+use crate::answers::Answers;
+use crate::{arg_value, load_file_lines};
 
-fn parse_array(line: &str) -> Vec<i32> {
+pub const VERSION: u32 = 2;
+
+fn parse_array(line: &str) -> Vec<i128> {
     let idx = line.chars().position(|c| c == ':').unwrap() + 1;
     line.chars()
         .skip(idx)
@@ -10,7 +13,7 @@ fn parse_array(line: &str) -> Vec<i32> {
         .collect()
 }
 
-fn parse_ignore_spaces(line: &str) -> i64 {
+fn parse_ignore_spaces(line: &str) -> i128 {
     let idx = line.chars().position(|c| c == ':').unwrap() + 1;
     let num = line
         .chars()
@@ -23,43 +26,170 @@ fn parse_ignore_spaces(line: &str) -> i64 {
     num.parse().unwrap()
 }
 
-pub fn run(args: &[String]) {
+/// Holding the button for `t` ms gives the boat a speed of
+/// `start_speed + acceleration * t` mm/ms for the rest of the race.
+#[derive(Debug, Clone, Copy)]
+struct Boat {
+    acceleration: i128,
+    start_speed: i128,
+}
+
+impl Boat {
+    pub fn new(acceleration: i128, start_speed: i128) -> Result<Self, String> {
+        if acceleration < 0 || start_speed < 0 {
+            return Err(format!(
+                "Acceleration and start speed can't be negative, got {acceleration} and {start_speed}"
+            ));
+        }
+        Ok(Self {
+            acceleration,
+            start_speed,
+        })
+    }
+
+    fn distance(&self, hold_time: i128, time_limit: i128) -> i128 {
+        (self.start_speed + self.acceleration * hold_time) * (time_limit - hold_time)
+    }
+
+    /// Counts the whole ms hold times in `[0, time_limit]` that beat the
+    /// record, i.e. go strictly farther.
+    pub fn ways_to_win(&self, time_limit: i128, record: i128) -> i128 {
+        let wins = |t: i128| self.distance(t, time_limit) > record;
+        let (a, v, d) = (self.acceleration, self.start_speed, record);
+
+        let (first, last) = if a == 0 {
+            if v == 0 {
+                return if wins(0) { time_limit + 1 } else { 0 };
+            }
+            // v * (time_limit - t) > d, so t is at most this
+            (0, (v * time_limit - d - 1).div_euclid(v))
+        } else {
+            // The distance beats the record strictly between the roots of
+            // a*t^2 - b*t + c = 0
+            let b = a * time_limit - v;
+            let c = d - v * time_limit;
+            let discriminant = b * b - 4 * a * c;
+            if discriminant <= 0 {
+                return 0;
+            }
+            // s <= sqrt(discriminant) < s + 1, which puts each boundary
+            // within a couple of whole numbers of these guesses
+            let s = discriminant.isqrt();
+            let low_guess = (b - s - 1).div_euclid(2 * a);
+            let high_guess = (b + s + 1).div_euclid(2 * a) + 1;
+            let Some(first) = (low_guess..=low_guess + 3).find(|t| wins(*t)) else {
+                return 0;
+            };
+            let last = (high_guess - 3..=high_guess)
+                .rev()
+                .find(|t| wins(*t))
+                .unwrap_or(first);
+            (first, last)
+        };
+        let first = i128::max(first, 0);
+        let last = i128::min(last, time_limit);
+        i128::max(last - first + 1, 0)
+    }
+}
+
+/// The boat settings change the answers, so they're cached like the input.
+pub fn cached_parts(args: &[String]) -> Option<Vec<usize>> {
+    args.chunks(2)
+        .all(|c| c.len() == 2 && (c[0] == "--accel" || c[0] == "--start-speed"))
+        .then(|| vec![1, 2])
+}
+
+pub fn run(args: &[String]) -> Answers {
     let lines = load_file_lines(&args[0]).unwrap();
 
+    let value_of = |name: &str, default: i128| {
+        arg_value(args, name).map_or(Ok(default), |v| {
+                v.parse().map_err(|_| format!("Bad value for {name}: {v}"))
+            })
+    };
+    let boat = value_of("--accel", 1)
+        .and_then(|accel| Ok((accel, value_of("--start-speed", 0)?)))
+        .and_then(|(accel, start_speed)| Boat::new(accel, start_speed));
+    let boat = match boat {
+        Ok(boat) => boat,
+        Err(msg) => {
+            println!("WARNING: {msg}");
+            let mut answers = Answers::default();
+            answers.warn(msg);
+            return answers;
+        }
+    };
+
     let times = parse_array(&lines[0]);
     let distances = parse_array(&lines[1]);
 
     let mut part_1_checksum = 1;
-    for i in 0..times.len() {
-        let time_limit = times[i];
-        let distance_goal = distances[i];
-
-        let mut sims_won = 0;
-        for t in 0..10000 {
-            let accel = 1;
-            let start_vel = t * accel;
-            let distance = start_vel * (time_limit - t);
-            if distance > distance_goal {
-                sims_won += 1;
-            }
-        }
-        part_1_checksum *= sims_won;
+    for (time_limit, distance_goal) in times.iter().zip(distances.iter()) {
+        part_1_checksum *= boat.ways_to_win(*time_limit, *distance_goal);
     }
     println!("Product of sims won (part 1): {part_1_checksum}");
 
     // Part 2!
-    // d = v*(tl - t)
-    // d = t*(tl - t)
-    // t^2 - tl*t + d = 0
     let time_limit = parse_ignore_spaces(&lines[0]);
     let distance_goal = parse_ignore_spaces(&lines[1]);
-    let a = 1.0;
-    let b = time_limit as f64;
-    let c = distance_goal as f64;
-    let min_accel_time = (-b - f64::sqrt(b * b - 4.0 * a * c)) / (2.0 * a);
-    let max_accel_time = (-b + f64::sqrt(b * b - 4.0 * a * c)) / (2.0 * a);
-    println!(
-        "Number of win sims: {}",
-        (f64::floor(max_accel_time) - f64::ceil(min_accel_time)) as i32 + 1
-    );
+    let num_win_sims = boat.ways_to_win(time_limit, distance_goal);
+    println!("Number of win sims: {}", num_win_sims);
+
+    Answers::new(part_1_checksum, num_win_sims)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(7, 9, 4)]
+    #[case(15, 40, 8)]
+    // The roots are exactly 10 and 20, which only tie the record
+    #[case(30, 200, 9)]
+    #[case(71530, 940200, 71503)]
+    #[case(0, 0, 0)]
+    #[case(10, 25, 0)]
+    #[case(10, 24, 1)]
+    fn test_ways_to_win(#[case] time_limit: i128, #[case] record: i128, #[case] expected: i128) {
+        let boat = Boat::new(1, 0).unwrap();
+        assert_eq!(boat.ways_to_win(time_limit, record), expected);
+    }
+
+    #[test]
+    fn test_beyond_f64_precision() {
+        let boat = Boat::new(1, 0).unwrap();
+        let time_limit: i128 = 1_000_000_000_000_000_007;
+        let k: i128 = 123_456_789;
+        // Holding for exactly k or time_limit - k ties the record
+        let record = k * (time_limit - k);
+        assert_eq!(boat.ways_to_win(time_limit, record), time_limit - 2 * k - 1);
+        assert_eq!(
+            boat.ways_to_win(time_limit, record - 1),
+            time_limit - 2 * k + 1
+        );
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        for acceleration in 0..4 {
+            for start_speed in 0..4 {
+                let boat = Boat::new(acceleration, start_speed).unwrap();
+                for time_limit in 0..25 {
+                    for record in -2..80 {
+                        let brute_force = (0..=time_limit)
+                            .filter(|t| boat.distance(*t, time_limit) > record)
+                            .count() as i128;
+                        assert_eq!(
+                            boat.ways_to_win(time_limit, record),
+                            brute_force,
+                            "{boat:?} time {time_limit} record {record}"
+                        );
+                    }
+                }
+            }
+        }
+        assert!(Boat::new(-1, 0).is_err());
+    }
 }
@@ -1,160 +1,272 @@
-use crate::load_file_lines;
-
-use std::cmp::Ordering;
-use std::collections::HashMap;
-
-// TODO Lol should be an enum
-mod hand_types {
-    pub const FIVE_OF_KIND: usize = 7;
-    pub const FOUR_OF_KIND: usize = 6;
-    pub const FULL_HOUSE: usize = 5;
-    pub const THREE_OF_KIND: usize = 4;
-    pub const TWO_PAIR: usize = 3;
-    pub const ONE_PAIR: usize = 2;
-    pub const HIGH_CARD: usize = 1;
+use crate::answers::Answers;
+use crate::{arg_value, load_file_lines};
+
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum HandType {
+    // In rank order, so the discriminant can go in a sort key
+    HighCard,
+    OnePair,
+    TwoPair,
+    ThreeOfKind,
+    FullHouse,
+    FourOfKind,
+    FiveOfKind,
 }
 
-struct Hand {
-    pub cards: Vec<char>,
-    pub bid: usize,
-    pub part_2: bool,
+impl HandType {
+    /// Classifies a hand by its two biggest groups of matching cards.
+    fn from_groups(largest: usize, second: usize) -> Self {
+        match (largest, second) {
+            (5.., _) => Self::FiveOfKind,
+            (4, _) => Self::FourOfKind,
+            (3, 2..) => Self::FullHouse,
+            (3, _) => Self::ThreeOfKind,
+            (2, 2..) => Self::TwoPair,
+            (2, _) => Self::OnePair,
+            _ => Self::HighCard,
+        }
+    }
 }
 
-impl Hand {
-    pub fn from_str(line: &str) -> Self {
-        let split: Vec<&str> = line.split(' ').collect();
+/// A hand's rank under one ruleset packed into a number: the hand type in
+/// the top bits, then each tie break card's value. Bigger keys win.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct SortKey(u128);
+
+/// How hands are ranked: which cards there are, which of them are wild and
+/// which card positions break ties between hands of the same type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Ruleset {
+    pub name: String,
+    /// Every card, weakest first
+    pub card_order: Vec<char>,
+    /// Cards that join whichever group makes the hand strongest
+    pub wildcards: Vec<char>,
+    pub hand_size: usize,
+    /// Card positions to compare, in order, when hand types are equal
+    pub tie_break: Vec<usize>,
+}
+
+impl Ruleset {
+    pub fn standard() -> Self {
         Self {
-            cards: split[0].chars().collect(),
-            bid: split[1].parse().unwrap(),
-            part_2: false,
+            name: "part 1".to_owned(),
+            card_order: "23456789TJQKA".chars().collect(),
+            wildcards: Vec::new(),
+            hand_size: 5,
+            tie_break: (0..5).collect(),
         }
     }
 
-    pub fn strength(&self) -> usize {
-        let mut counts = HashMap::<char, usize>::new();
-        for c in self.cards.iter() {
-            if !counts.contains_key(c) {
-                counts.insert(*c, 0);
+    /// Jacks become jokers: wild, but the weakest card on their own.
+    pub fn jokers() -> Self {
+        Self {
+            name: "part 2".to_owned(),
+            card_order: "J23456789TQKA".chars().collect(),
+            wildcards: vec!['J'],
+            ..Self::standard()
+        }
+    }
+
+    /// Reads rulesets from a config file made of sections like:
+    ///
+    /// ```text
+    /// [two jokers]
+    /// cards = J23456789TQKA
+    /// wildcards = J2
+    /// hand_size = 5
+    /// tie_break = 0 1 2 3 4
+    /// ```
+    ///
+    /// Anything left out is the same as the part 1 rules. Lines starting
+    /// with `#` are comments.
+    pub fn parse_config(text: &str) -> Result<Vec<Self>, String> {
+        let mut rulesets: Vec<Self> = Vec::new();
+        // Whether each ruleset set its own tie break, otherwise it follows the hand size
+        let mut tie_break_given = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: String| format!("Line {}: {msg}", i + 1);
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                rulesets.push(Self {
+                    name: name.trim().to_owned(),
+                    ..Self::standard()
+                });
+                tie_break_given.push(false);
+                continue;
+            }
+            let Some(ruleset) = rulesets.last_mut() else {
+                return Err(err("Expected a [name] before any settings".to_owned()));
+            };
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(err(format!("Expected key = value, got {line}")));
+            };
+            let value = value.trim();
+            match key.trim() {
+                "cards" => ruleset.card_order = value.chars().collect(),
+                "wildcards" => ruleset.wildcards = value.chars().collect(),
+                "hand_size" => {
+                    ruleset.hand_size = value
+                        .parse()
+                        .map_err(|_| err(format!("Bad hand size {value}")))?
+                }
+                "tie_break" => {
+                    ruleset.tie_break = value
+                        .split_whitespace()
+                        .map(|p| p.parse().map_err(|_| err(format!("Bad card position {p}"))))
+                        .collect::<Result<_, _>>()?;
+                    *tie_break_given.last_mut().unwrap() = true;
+                }
+                other => return Err(err(format!("Unknown setting {other}"))),
             }
-            *counts.get_mut(c).unwrap() += 1;
         }
-        let raw_longest: usize = if self.part_2 {
-            *counts
-                .iter()
-                .filter_map(|(k, v)| match k {
-                    'J' => None,
-                    _ => Some(v),
-                })
-                .max()
-                .unwrap_or(&0)
-        } else {
-            *counts.values().max().unwrap()
-        };
-        let j_counts = *counts.get(&'J').unwrap_or(&0);
-        let longest = if self.part_2 {
-            raw_longest + j_counts
-        } else {
-            raw_longest
-        };
-        let num_unique = if self.part_2 {
-            counts.len() - if j_counts > 0 { 1 } else { 0 }
-        } else {
-            counts.len()
-        };
-        if longest == 5 {
-            hand_types::FIVE_OF_KIND
-        } else if longest == 4 {
-            hand_types::FOUR_OF_KIND
-        } else if longest == 3 && num_unique == 2 {
-            hand_types::FULL_HOUSE
-        } else if longest == 3 && num_unique == 3 {
-            hand_types::THREE_OF_KIND
-        } else if longest == 2 && num_unique == 3 {
-            hand_types::TWO_PAIR
-        } else if longest == 2 && num_unique == 4 {
-            hand_types::ONE_PAIR
-        } else if longest == 1 {
-            hand_types::HIGH_CARD
-        } else {
-            panic!("Unhandled case! {}", self.cards.iter().collect::<String>());
+        for (ruleset, tie_break_given) in rulesets.iter_mut().zip(tie_break_given) {
+            if !tie_break_given {
+                ruleset.tie_break = (0..ruleset.hand_size).collect();
+            }
+            ruleset.validate()?;
         }
+        Ok(rulesets)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let name = &self.name;
+        if self.hand_size == 0 {
+            return Err(format!("{name}: hands need at least one card"));
+        }
+        if self.card_order.is_empty() {
+            return Err(format!("{name}: there are no cards"));
+        }
+        // Leaves room for the hand type in a sort key
+        if self.tie_break.len() as u32 * self.bits_per_card() > 124 {
+            return Err(format!("{name}: too many tie break cards to rank hands"));
+        }
+        for (i, c) in self.card_order.iter().enumerate() {
+            if self.card_order[..i].contains(c) {
+                return Err(format!("{name}: card {c} is listed twice"));
+            }
+        }
+        if let Some(c) = self.wildcards.iter().find(|c| !self.card_order.contains(c)) {
+            return Err(format!("{name}: wildcard {c} isn't one of the cards"));
+        }
+        if let Some(p) = self.tie_break.iter().find(|p| **p >= self.hand_size) {
+            return Err(format!("{name}: there's no card position {p} in a hand"));
+        }
+        Ok(())
     }
-}
 
-impl PartialEq for Hand {
-    fn eq(&self, other: &Self) -> bool {
-        self.strength() == other.strength()
+    fn card_value(&self, c: char) -> Option<usize> {
+        self.card_order.iter().position(|o| *o == c)
+    }
+
+    fn bits_per_card(&self) -> u32 {
+        match self.card_order.len() {
+            0 | 1 => 0,
+            n => usize::BITS - (n - 1).leading_zeros(),
+        }
     }
-}
 
-impl Eq for Hand {}
-
-fn card_value(c: char, is_part_2: bool) -> usize {
-    match c {
-        '2' => 1,
-        '3' => 2,
-        '4' => 3,
-        '5' => 4,
-        '6' => 5,
-        '7' => 6,
-        '8' => 7,
-        '9' => 8,
-        'T' => 9,
-        'J' => {
-            if is_part_2 {
-                0
-            } else {
-                10
+    /// Checks a hand can be played under these rules.
+    pub fn check(&self, hand: &Hand) -> Result<(), String> {
+        let cards: String = hand.cards.iter().collect();
+        if hand.cards.len() != self.hand_size {
+            return Err(format!(
+                "{}: {cards} doesn't have {} cards",
+                self.name, self.hand_size
+            ));
+        }
+        if let Some(c) = hand.cards.iter().find(|c| self.card_value(**c).is_none()) {
+            return Err(format!("{}: {cards} has an unknown card {c}", self.name));
+        }
+        Ok(())
+    }
+
+    /// Expects a hand that passed `check`.
+    pub fn hand_type(&self, hand: &Hand) -> HandType {
+        let mut counts = vec![0; self.card_order.len()];
+        let mut num_wild = 0;
+        for c in hand.cards.iter() {
+            if self.wildcards.contains(c) {
+                num_wild += 1;
+            } else if let Some(value) = self.card_value(*c) {
+                counts[value] += 1;
             }
         }
-        'Q' => 11,
-        'K' => 12,
-        'A' => 13,
-        _ => 0, // Bad card
+        let (mut largest, mut second) = (0, 0);
+        for count in counts {
+            if count > largest {
+                (largest, second) = (count, largest);
+            } else if count > second {
+                second = count;
+            }
+        }
+        HandType::from_groups(largest + num_wild, second)
     }
-}
 
-impl PartialOrd for Hand {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    /// Expects a hand that passed `check`.
+    pub fn sort_key(&self, hand: &Hand) -> SortKey {
+        let bits = self.bits_per_card();
+        let mut key = self.hand_type(hand) as u128;
+        for p in self.tie_break.iter() {
+            key = (key << bits) | self.card_value(hand.cards[*p]).unwrap_or(0) as u128;
+        }
+        SortKey(key)
     }
 }
 
-impl Ord for Hand {
-    fn cmp(&self, other: &Self) -> Ordering {
-        match self.strength().cmp(&other.strength()) {
-            Ordering::Greater => Ordering::Greater,
-            Ordering::Less => Ordering::Less,
-            Ordering::Equal => {
-                for (s, o) in self
-                    .cards
-                    .iter()
-                    .map(|c| card_value(*c, self.part_2))
-                    .zip(other.cards.iter().map(|c| card_value(*c, self.part_2)))
-                {
-                    match s.cmp(&o) {
-                        Ordering::Greater => return Ordering::Greater,
-                        Ordering::Less => return Ordering::Less,
-                        Ordering::Equal => (),
-                    }
-                }
-                Ordering::Equal
-            }
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Hand {
+    pub cards: Vec<char>,
+    pub bid: usize,
+}
+
+impl Hand {
+    pub fn from_str(line: &str) -> Self {
+        let split: Vec<&str> = line.split(' ').collect();
+        Self {
+            cards: split[0].chars().collect(),
+            bid: split[1].parse().unwrap(),
         }
     }
 }
 
-fn calc_total_winnings(hands: &[Hand]) -> usize {
+fn calc_total_winnings(hands: &[&Hand]) -> usize {
     let mut bid_sum: usize = 0;
-    let mut bid_multiplier = 1;
-    for bid in hands.iter().map(|h| h.bid) {
+    for (bid_multiplier, bid) in (1..).zip(hands.iter().map(|h| h.bid)) {
         bid_sum += bid * bid_multiplier;
-        bid_multiplier += 1;
     }
     bid_sum
 }
 
-pub fn run(args: &[String]) {
+/// Ranks the hands under a ruleset and adds up the winnings.
+fn play(hands: &[Hand], rules: &Ruleset, debug: bool) -> Result<usize, String> {
+    for h in hands.iter() {
+        rules.check(h)?;
+    }
+    // Each key is worked out once, and equal hands keep their input order
+    let mut keys: Vec<(SortKey, usize)> = hands
+        .iter()
+        .enumerate()
+        .map(|(i, h)| (rules.sort_key(h), i))
+        .collect();
+    keys.sort_unstable();
+    let ranked: Vec<&Hand> = keys.iter().map(|(_, i)| &hands[*i]).collect();
+    if debug {
+        println!("Sorted hands ({}):", rules.name);
+        for h in ranked.iter() {
+            println!("{} {}", h.cards.iter().collect::<String>(), h.bid);
+        }
+    }
+    Ok(calc_total_winnings(&ranked))
+}
+
+pub fn run(args: &[String]) -> Answers {
+    let debug = args.contains(&"debug".to_owned());
     let lines = load_file_lines(&args[0]).unwrap();
 
     let mut hands = Vec::with_capacity(lines.len());
@@ -162,103 +274,239 @@ pub fn run(args: &[String]) {
         hands.push(Hand::from_str(line));
     }
 
-    hands.sort();
-    if args.contains(&"debug".to_owned()) {
-        println!("Sorted hands:");
-        for h in hands.iter() {
-            println!("{} {}", h.cards.iter().collect::<String>(), h.bid);
+    let mut answers = Answers::default();
+    match play(&hands, &Ruleset::standard(), debug) {
+        Ok(winnings) => {
+            println!("Total winnings (part 1): {}", winnings);
+            answers.set(1, winnings);
         }
+        Err(msg) => answers.warn(msg),
     }
-
-    println!("Total winnings (part 1): {}", calc_total_winnings(&hands));
-
-    for h in hands.iter_mut() {
-        h.part_2 = true;
+    match play(&hands, &Ruleset::jokers(), debug) {
+        Ok(winnings) => {
+            println!("Total winnings (part 2): {}", winnings);
+            answers.set(2, winnings);
+        }
+        Err(msg) => answers.warn(msg),
     }
-    hands.sort();
 
-    println!("Total winnings (part 2): {}", calc_total_winnings(&hands));
+    if let Some(path) = arg_value(args, "--rules") {
+        let rulesets = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {path}: {e}"))
+            .and_then(|text| Ruleset::parse_config(&text));
+        match rulesets {
+            Ok(rulesets) => {
+                for rules in rulesets.iter() {
+                    match play(&hands, rules, debug) {
+                        Ok(winnings) => println!("Total winnings ({}): {}", rules.name, winnings),
+                        Err(msg) => answers.warn(msg),
+                    }
+                }
+            }
+            Err(msg) => answers.warn(msg),
+        }
+    }
+    for msg in answers.diagnostics.iter() {
+        println!("WARNING: {msg}");
+    }
+    answers
 }
 
 #[cfg(test)]
 pub mod test {
     use super::*;
     use rstest::rstest;
+    use std::cmp::Ordering;
+
+    fn compare(rules: &Ruleset, a: &str, b: &str) -> Ordering {
+        rules.sort_key(&hand(a)).cmp(&rules.sort_key(&hand(b)))
+    }
+
+    fn hand(cards: &str) -> Hand {
+        Hand {
+            cards: cards.chars().collect(),
+            bid: 0,
+        }
+    }
 
     #[rstest]
-    #[case("AAAAA", hand_types::FIVE_OF_KIND)]
-    #[case("AA8AA", hand_types::FOUR_OF_KIND)]
-    #[case("23332", hand_types::FULL_HOUSE)]
-    #[case("TTT98", hand_types::THREE_OF_KIND)]
-    #[case("23432", hand_types::TWO_PAIR)]
-    #[case("A23A4", hand_types::ONE_PAIR)]
-    #[case("23456", hand_types::HIGH_CARD)]
-    #[case("JJ7K7", hand_types::TWO_PAIR)]
-    fn test_card_types(#[case] hand: &str, #[case] hand_type: usize) {
-        assert_eq!(
-            Hand {
-                cards: hand.chars().collect(),
-                bid: 0,
-                part_2: false,
-            }
-            .strength(),
-            hand_type
-        );
+    #[case("AAAAA", HandType::FiveOfKind)]
+    #[case("AA8AA", HandType::FourOfKind)]
+    #[case("23332", HandType::FullHouse)]
+    #[case("TTT98", HandType::ThreeOfKind)]
+    #[case("23432", HandType::TwoPair)]
+    #[case("A23A4", HandType::OnePair)]
+    #[case("23456", HandType::HighCard)]
+    #[case("JJ7K7", HandType::TwoPair)]
+    fn test_card_types(#[case] cards: &str, #[case] hand_type: HandType) {
+        assert_eq!(Ruleset::standard().hand_type(&hand(cards)), hand_type);
     }
 
     #[rstest]
-    #[case("QJJQQ", hand_types::FIVE_OF_KIND)]
-    #[case("QJJQ2", hand_types::FOUR_OF_KIND)]
-    #[case("AAAAA", hand_types::FIVE_OF_KIND)]
-    #[case("AAAAJ", hand_types::FIVE_OF_KIND)]
-    #[case("AAAJJ", hand_types::FIVE_OF_KIND)]
-    #[case("AAJJJ", hand_types::FIVE_OF_KIND)]
-    #[case("AJJJJ", hand_types::FIVE_OF_KIND)]
-    #[case("JJJJJ", hand_types::FIVE_OF_KIND)]
-    #[case("AA8AA", hand_types::FOUR_OF_KIND)]
-    #[case("AA8JA", hand_types::FOUR_OF_KIND)]
-    #[case("AA8JJ", hand_types::FOUR_OF_KIND)]
-    #[case("23332", hand_types::FULL_HOUSE)]
-    #[case("23J32", hand_types::FULL_HOUSE)]
-    #[case("2333J", hand_types::FOUR_OF_KIND)]
-    #[case("JJ332", hand_types::FOUR_OF_KIND)]
-    #[case("TTT98", hand_types::THREE_OF_KIND)]
-    #[case("TTJ98", hand_types::THREE_OF_KIND)]
-    #[case("TTTJ8", hand_types::FOUR_OF_KIND)]
-    #[case("23432", hand_types::TWO_PAIR)]
-    #[case("J3432", hand_types::THREE_OF_KIND)]
-    #[case("23J32", hand_types::FULL_HOUSE)]
-    #[case("A23A4", hand_types::ONE_PAIR)]
-    #[case("A23J4", hand_types::ONE_PAIR)]
-    #[case("A23AJ", hand_types::THREE_OF_KIND)]
-    #[case("23456", hand_types::HIGH_CARD)]
-    #[case("J3456", hand_types::ONE_PAIR)]
-    #[case("JJ7K7", hand_types::FOUR_OF_KIND)]
-    fn test_card_types_part_2(#[case] hand: &str, #[case] hand_type: usize) {
-        assert_eq!(
-            Hand {
-                cards: hand.chars().collect(),
-                bid: 0,
-                part_2: true,
-            }
-            .strength(),
-            hand_type
-        );
+    #[case("QJJQQ", HandType::FiveOfKind)]
+    #[case("QJJQ2", HandType::FourOfKind)]
+    #[case("AAAAA", HandType::FiveOfKind)]
+    #[case("AAAAJ", HandType::FiveOfKind)]
+    #[case("AAAJJ", HandType::FiveOfKind)]
+    #[case("AAJJJ", HandType::FiveOfKind)]
+    #[case("AJJJJ", HandType::FiveOfKind)]
+    #[case("JJJJJ", HandType::FiveOfKind)]
+    #[case("AA8AA", HandType::FourOfKind)]
+    #[case("AA8JA", HandType::FourOfKind)]
+    #[case("AA8JJ", HandType::FourOfKind)]
+    #[case("23332", HandType::FullHouse)]
+    #[case("23J32", HandType::FullHouse)]
+    #[case("2333J", HandType::FourOfKind)]
+    #[case("JJ332", HandType::FourOfKind)]
+    #[case("TTT98", HandType::ThreeOfKind)]
+    #[case("TTJ98", HandType::ThreeOfKind)]
+    #[case("TTTJ8", HandType::FourOfKind)]
+    #[case("23432", HandType::TwoPair)]
+    #[case("J3432", HandType::ThreeOfKind)]
+    #[case("23J32", HandType::FullHouse)]
+    #[case("A23A4", HandType::OnePair)]
+    #[case("A23J4", HandType::OnePair)]
+    #[case("A23AJ", HandType::ThreeOfKind)]
+    #[case("23456", HandType::HighCard)]
+    #[case("J3456", HandType::OnePair)]
+    #[case("JJ7K7", HandType::FourOfKind)]
+    fn test_card_types_part_2(#[case] cards: &str, #[case] hand_type: HandType) {
+        assert_eq!(Ruleset::jokers().hand_type(&hand(cards)), hand_type);
     }
 
     #[rstest]
     #[case("JKKK2", "QQQQ2")]
     fn test_card_order_part_2(#[case] lesser: &str, #[case] greater: &str) {
-        assert!(
-            Hand {
-                cards: lesser.chars().collect(),
-                bid: 0,
-                part_2: true,
-            } < Hand {
-                cards: greater.chars().collect(),
-                bid: 0,
-                part_2: true,
+        assert_eq!(compare(&Ruleset::jokers(), lesser, greater), Ordering::Less);
+    }
+
+    #[test]
+    fn test_example_winnings() {
+        let hands: Vec<Hand> = [
+            "32T3K 765",
+            "T55J5 684",
+            "KK677 28",
+            "KTJJT 220",
+            "QQQJA 483",
+        ]
+        .iter()
+        .map(|l| Hand::from_str(l))
+        .collect();
+        assert_eq!(play(&hands, &Ruleset::standard(), false), Ok(6440));
+        assert_eq!(play(&hands, &Ruleset::jokers(), false), Ok(5905));
+    }
+
+    #[test]
+    fn test_config_rulesets() {
+        let config = "
+            # Deuces are wild too
+            [two wilds]
+            cards = 2J3456789TQKA
+            wildcards = J2
+
+            [three cards]
+            cards = ABC
+            hand_size = 3
+            tie_break = 2 1 0
+        ";
+        let rulesets = Ruleset::parse_config(config).unwrap();
+        assert_eq!(rulesets.len(), 2);
+        assert_eq!(rulesets[0].name, "two wilds");
+        assert_eq!(rulesets[0].tie_break, vec![0, 1, 2, 3, 4]);
+        assert_eq!(rulesets[0].hand_type(&hand("2J3K5")), HandType::ThreeOfKind);
+        assert_eq!(rulesets[0].hand_type(&hand("22JJ5")), HandType::FiveOfKind);
+
+        let three = &rulesets[1];
+        assert_eq!(three.hand_type(&hand("AAB")), HandType::OnePair);
+        assert_eq!(three.hand_type(&hand("CCC")), HandType::ThreeOfKind);
+        // Both one pair, so the last card decides first
+        assert_eq!(compare(three, "BCC", "CAA"), Ordering::Greater);
+        assert!(three.check(&hand("ABCA")).is_err());
+        assert!(three.check(&hand("ABD")).is_err());
+    }
+
+    #[rstest]
+    #[case("cards = 23")]
+    #[case("[a]\nwildcards = X")]
+    #[case("[a]\ncards = 223")]
+    #[case("[a]\nhand_size = 3\ntie_break = 0 3")]
+    #[case("[a]\nhand_size = five")]
+    #[case("[a]\ncolour = red")]
+    fn test_config_errors(#[case] config: &str) {
+        assert!(Ruleset::parse_config(config).is_err());
+    }
+
+    #[test]
+    fn test_sort_key_consistent_with_eq() {
+        let rules = Ruleset::jokers();
+        // Different hands of the same rank tie, but aren't equal
+        assert_eq!(compare(&rules, "JJJJJ", "JJJJJ"), Ordering::Equal);
+        assert_ne!(hand("KK677"), hand("KK767"));
+        assert!(rules.sort_key(&hand("KK677")) < rules.sort_key(&hand("KK767")));
+        assert!(rules.sort_key(&hand("JJJJJ")) < rules.sort_key(&hand("22222")));
+        assert!(rules.sort_key(&hand("AKQT9")) < rules.sort_key(&hand("22345")));
+    }
+
+    /// The ranking before sort keys, for the benchmark: every comparison
+    /// works out both hand types from scratch.
+    fn compare_without_keys(rules: &Ruleset, a: &Hand, b: &Hand) -> Ordering {
+        let hand_type = |h: &Hand| {
+            let mut counts = std::collections::HashMap::<char, usize>::new();
+            for c in h.cards.iter().filter(|c| !rules.wildcards.contains(c)) {
+                *counts.entry(*c).or_insert(0) += 1;
             }
-        );
+            let num_wild = h.cards.len() - counts.values().sum::<usize>();
+            let mut groups: Vec<usize> = counts.into_values().collect();
+            groups.sort_unstable_by(|a, b| b.cmp(a));
+            HandType::from_groups(
+                groups.first().unwrap_or(&0) + num_wild,
+                *groups.get(1).unwrap_or(&0),
+            )
+        };
+        hand_type(a).cmp(&hand_type(b)).then_with(|| {
+            a.cards
+                .iter()
+                .zip(b.cards.iter())
+                .map(|(x, y)| rules.card_value(*x).cmp(&rules.card_value(*y)))
+                .find(|o| o.is_ne())
+                .unwrap_or(Ordering::Equal)
+        })
+    }
+
+    // Run with `cargo test --release bench_million_hands -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_million_hands() {
+        let mut seed: u64 = 2023;
+        let mut next = move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize
+        };
+        let cards: Vec<char> = "23456789TJQKA".chars().collect();
+        let hands: Vec<Hand> = (0..1_000_000)
+            .map(|_| Hand {
+                cards: (0..5).map(|_| cards[next() % cards.len()]).collect(),
+                bid: next() % 1000 + 1,
+            })
+            .collect();
+
+        for rules in [Ruleset::standard(), Ruleset::jokers()] {
+            let start = std::time::Instant::now();
+            let mut sorted: Vec<&Hand> = hands.iter().collect();
+            sorted.sort_by(|a, b| compare_without_keys(&rules, a, b));
+            let without_keys = start.elapsed();
+
+            let start = std::time::Instant::now();
+            let winnings = play(&hands, &rules, false).unwrap();
+            let with_keys = start.elapsed();
+
+            assert_eq!(winnings, calc_total_winnings(&sorted));
+            println!(
+                "{}: {:?} comparing hand types, {:?} with sort keys",
+                rules.name, without_keys, with_keys
+            );
+        }
     }
 }
@@ -1,10 +1,13 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
-use crate::load_file_lines;
+use crate::answers::Answers;
+use crate::{arg_value, load_file_lines};
 
 use lazy_static::lazy_static;
 use regex::Regex;
 
+pub const VERSION: u32 = 2;
+
 enum Direction {
     Left,
     Right,
@@ -20,7 +23,7 @@ impl Node {
     pub fn from_str(line: &str) -> Result<Self, String> {
         lazy_static! {
             static ref RE: Regex = Regex::new(
-                r"(?<name>[A-Z0-9]{3}) = \((?<left>[A-Z0-9]{3}), (?<right>[A-Z0-9]{3})\)"
+                r"^(?<name>[^\s=(),]+) = \((?<left>[^\s=(),]+), (?<right>[^\s=(),]+)\)$"
             )
             .unwrap();
         };
@@ -36,74 +39,294 @@ impl Node {
     }
 }
 
-fn count_steps_to_z<'n>(
-    directions: &[Direction],
-    nodes: &'n [Node],
-    start: &'n str,
-) -> HashMap<&'n str, usize> {
-    let mut i = 0;
-    let mut curr_name = start;
-    let mut step_counts = HashMap::new();
-    let mut num_steps = 0;
-    loop {
-        if i >= directions.len() {
-            i = 0;
-        }
-        let curr_node = nodes.iter().find(|n| n.name == curr_name).unwrap();
-        curr_name = match directions[i] {
-            Direction::Left => &curr_node.left,
-            Direction::Right => &curr_node.right,
+/// The nodes with their names swapped for indices, so a step is a lookup.
+struct Network {
+    names: Vec<String>,
+    index: HashMap<String, usize>,
+    left: Vec<usize>,
+    right: Vec<usize>,
+}
+
+impl Network {
+    /// Fails with every duplicate node and reference to an unknown node.
+    pub fn from_nodes(nodes: &[Node]) -> Result<Self, Vec<String>> {
+        let mut errors = Vec::new();
+        let mut index = HashMap::with_capacity(nodes.len());
+        for (i, node) in nodes.iter().enumerate() {
+            if index.insert(node.name.clone(), i).is_some() {
+                errors.push(format!("Node {} is declared twice", node.name));
+            }
+        }
+        let mut lookup = |from: &str, to: &str| match index.get(to) {
+            Some(i) => *i,
+            None => {
+                errors.push(format!("Node {from} leads to unknown node {to}"));
+                0
+            }
         };
-        if curr_name.ends_with('Z') {
-            if step_counts.contains_key(curr_name) {
-                break;
+        let left: Vec<usize> = nodes.iter().map(|n| lookup(&n.name, &n.left)).collect();
+        let right: Vec<usize> = nodes.iter().map(|n| lookup(&n.name, &n.right)).collect();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(Self {
+            names: nodes.iter().map(|n| n.name.clone()).collect(),
+            index,
+            left,
+            right,
+        })
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.index.get(name).copied()
+    }
+
+    pub fn name(&self, node: usize) -> &str {
+        &self.names[node]
+    }
+
+    pub fn next(&self, node: usize, direction: &Direction) -> usize {
+        match direction {
+            Direction::Left => self.left[node],
+            Direction::Right => self.right[node],
+        }
+    }
+
+    /// Follows the directions from `start` until a (node, instruction) state
+    /// repeats. Returns the node at each step before that, and the step the
+    /// repeated state was first seen at.
+    pub fn walk(&self, directions: &[Direction], start: usize) -> (Vec<usize>, usize) {
+        let mut seen = HashMap::new();
+        let mut path = Vec::new();
+        let mut node = start;
+        loop {
+            let instruction = path.len() % directions.len();
+            if let Some(first_seen) = seen.insert((node, instruction), path.len()) {
+                return (path, first_seen);
+            }
+            path.push(node);
+            node = self.next(node, &directions[instruction]);
+        }
+    }
+
+    /// Every node that can be reached from `starts`, in either direction.
+    pub fn reachable(&self, starts: &[usize]) -> Vec<bool> {
+        let mut reached = vec![false; self.names.len()];
+        let mut queue = starts.to_vec();
+        while let Some(node) = queue.pop() {
+            if !reached[node] {
+                reached[node] = true;
+                queue.push(self.left[node]);
+                queue.push(self.right[node]);
             }
-            step_counts.insert(curr_name, num_steps + 1);
         }
-        i += 1;
-        num_steps += 1;
+        reached
     }
-    step_counts
 }
 
-fn get_factors(x: usize) -> Vec<usize> {
-    let mut factors = Vec::new();
-    let mut x_value = x;
-    // TODO Maybe there's a better way to get multiples
-    for i in 2..(x + 1) {
-        if x_value % i == 0 {
-            factors.push(i);
-            x_value /= i;
+/// The step counts at which a walk stands on an end node: each of `finite`,
+/// plus every step from `cycle_start` on that is one of `residues` modulo
+/// `period`.
+#[derive(Debug, Clone, PartialEq)]
+struct Arrivals {
+    finite: Vec<u64>,
+    cycle_start: u64,
+    period: u64,
+    residues: Vec<u64>,
+}
+
+impl Arrivals {
+    /// Notes every step of the walk from `start` that lands on an end node.
+    pub fn walk(
+        network: &Network,
+        directions: &[Direction],
+        start: usize,
+        is_end: impl Fn(usize) -> bool,
+    ) -> Self {
+        let (path, cycle_start) = network.walk(directions, start);
+        let step = path.len() as u64;
+        let cycle_start = cycle_start as u64;
+        let hits = (0..step).filter(|s| is_end(path[*s as usize]));
+        let period = step - cycle_start;
+        let (finite, cycle): (Vec<u64>, Vec<u64>) = hits.partition(|h| *h < cycle_start);
+        let mut residues: Vec<u64> = cycle.iter().map(|h| h % period).collect();
+        residues.sort();
+        Self {
+            finite,
+            cycle_start,
+            period,
+            residues,
         }
     }
-    factors
+
+    fn in_cycle(&self, step: u64) -> bool {
+        step >= self.cycle_start && self.residues.contains(&(step % self.period))
+    }
+
+    pub fn contains(&self, step: u64) -> bool {
+        self.finite.contains(&step) || self.in_cycle(step)
+    }
+
+    pub fn earliest(&self) -> Option<u64> {
+        let start = self.cycle_start;
+        let periodic = self
+            .residues
+            .iter()
+            .map(|r| start + (r + self.period - start % self.period) % self.period);
+        self.finite.iter().copied().chain(periodic).min()
+    }
+
+    /// The steps at which both walks stand on end nodes.
+    pub fn intersect(&self, other: &Self) -> Result<Self, String> {
+        let mut finite: Vec<u64> = self
+            .finite
+            .iter()
+            .filter(|f| other.contains(**f))
+            .chain(other.finite.iter().filter(|f| self.in_cycle(**f)))
+            .copied()
+            .collect();
+        finite.sort();
+        finite.dedup();
+
+        let gcd = greatest_common_divisor(self.period, other.period);
+        let period = (self.period / gcd)
+            .checked_mul(other.period)
+            .ok_or("The combined cycle length overflows")?;
+        let mut residues: Vec<u64> = self
+            .residues
+            .iter()
+            .flat_map(|a| {
+                other
+                    .residues
+                    .iter()
+                    .filter_map(|b| chinese_remainder(*a, self.period, *b, other.period))
+            })
+            .collect();
+        residues.sort();
+        residues.dedup();
+
+        Ok(Self {
+            finite,
+            cycle_start: self.cycle_start.max(other.cycle_start),
+            period,
+            residues,
+        })
+    }
 }
 
-fn get_least_common_multiple(values: &[usize]) -> usize {
-    let mut gc_factors = Vec::<usize>::new();
-    for (a, b) in values
-        .iter()
-        .take(values.len() - 1)
-        .zip(values.iter().skip(1))
-    {
-        gc_factors.push(
-            **get_factors(*a)
-                .iter()
-                .collect::<HashSet<&usize>>()
-                .union(&get_factors(*b).iter().collect::<HashSet<&usize>>())
-                .max()
-                .unwrap_or(&&1),
-        );
+fn greatest_common_divisor(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        greatest_common_divisor(b, a % b)
     }
+}
 
-    let mut lcm = values[0];
-    for (x, gcf) in values.iter().skip(1).zip(gc_factors.iter()) {
-        lcm *= x / gcf;
+/// Returns `(g, x, y)` with `a * x + b * y = g = gcd(a, b)`.
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a, 1, 0)
+    } else {
+        let (g, x, y) = extended_gcd(b, a % b);
+        (g, y, x - (a / b) * y)
     }
-    lcm
 }
 
-pub fn run(args: &[String]) {
+/// Finds the `x` below `lcm(m, n)` with `x = a (mod m)` and `x = b (mod n)`,
+/// if there is one. The moduli don't have to be coprime.
+fn chinese_remainder(a: u64, m: u64, b: u64, n: u64) -> Option<u64> {
+    let (a, m, b, n) = (a as i128, m as i128, b as i128, n as i128);
+    let (g, p, _) = extended_gcd(m, n);
+    if (b - a) % g != 0 {
+        return None;
+    }
+    let lcm = m / g * n;
+    let k = ((b - a) / g * p).rem_euclid(n / g);
+    Some((a + m * k).rem_euclid(lcm) as u64)
+}
+
+const CYCLE_COLORS: [&str; 8] = [
+    "red",
+    "blue",
+    "darkgreen",
+    "darkorange",
+    "purple",
+    "deeppink",
+    "cyan4",
+    "goldenrod",
+];
+
+fn dot_id(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Writes the network as a Graphviz graph, drawing the loop each of `starts`
+/// ends up walking in its own color. With `reachable_only` the nodes those
+/// starts can never get to are left out.
+fn to_dot(
+    network: &Network,
+    directions: &[Direction],
+    starts: &[usize],
+    reachable_only: bool,
+) -> String {
+    let shown = if reachable_only {
+        network.reachable(starts)
+    } else {
+        vec![true; network.names.len()]
+    };
+
+    // Colors for each (node, goes right) edge taken inside a start's loop
+    let mut cycle_colors: HashMap<(usize, bool), Vec<&str>> = HashMap::new();
+    for (i, start) in starts.iter().enumerate() {
+        let color = CYCLE_COLORS[i % CYCLE_COLORS.len()];
+        let (path, cycle_start) = network.walk(directions, *start);
+        for (step, node) in path.iter().enumerate().skip(cycle_start) {
+            let right = matches!(directions[step % directions.len()], Direction::Right);
+            let colors = cycle_colors.entry((*node, right)).or_default();
+            if !colors.contains(&color) {
+                colors.push(color);
+            }
+        }
+    }
+
+    let mut dot = String::from("digraph network {\n");
+    for (node, name) in network.names.iter().enumerate() {
+        if !shown[node] {
+            continue;
+        }
+        let style = if name.ends_with('A') {
+            " [shape=box, style=filled, fillcolor=palegreen]"
+        } else if name.ends_with('Z') {
+            " [shape=doublecircle, style=filled, fillcolor=lightpink]"
+        } else {
+            ""
+        };
+        dot += &format!("    {}{style};\n", dot_id(name));
+    }
+    for (node, name) in network.names.iter().enumerate() {
+        if !shown[node] {
+            continue;
+        }
+        for (right, label, to) in [
+            (false, "L", network.left[node]),
+            (true, "R", network.right[node]),
+        ] {
+            let highlight = match cycle_colors.get(&(node, right)) {
+                Some(colors) => format!(", color=\"{}\", penwidth=2", colors.join(":")),
+                None => String::new(),
+            };
+            dot += &format!(
+                "    {} -> {} [label={label}{highlight}];\n",
+                dot_id(name),
+                dot_id(network.name(to))
+            );
+        }
+    }
+    dot += "}\n";
+    dot
+}
+
+pub fn run(args: &[String]) -> Answers {
     let lines = load_file_lines(&args[0]).unwrap();
     let directions: Vec<Direction> = lines[0]
         .chars()
@@ -115,48 +338,306 @@ pub fn run(args: &[String]) {
             }
         })
         .collect();
-    let nodes: Vec<Node> = lines
-        .iter()
-        .skip(2)
-        .filter_map(|line| Node::from_str(line).ok())
-        .collect();
+    let mut answers = Answers::default();
+    let mut nodes: Vec<Node> = Vec::with_capacity(lines.len());
+    for line in lines.iter().skip(2).filter(|l| !l.is_empty()) {
+        match Node::from_str(line) {
+            Ok(node) => nodes.push(node),
+            Err(msg) => {
+                println!("WARNING: {msg}");
+                answers.warn(msg);
+            }
+        }
+    }
+
+    let network = match Network::from_nodes(&nodes) {
+        Ok(network) => network,
+        Err(errors) => {
+            for msg in errors {
+                println!("WARNING: {msg}");
+                answers.warn(msg);
+            }
+            return answers;
+        }
+    };
+
+    if directions.is_empty() {
+        let msg = "There are no instructions to follow";
+        println!("WARNING: {msg}");
+        answers.warn(msg);
+        return answers;
+    }
+
+    if let (Some(start), Some(end)) = (network.index_of("AAA"), network.index_of("ZZZ")) {
+        match Arrivals::walk(&network, &directions, start, |n| n == end).earliest() {
+            Some(num_steps) => {
+                println!("Number of steps (part 1): {}", num_steps);
+                answers.set(1, num_steps);
+            }
+            None => {
+                let msg = "AAA never reaches ZZZ";
+                println!("WARNING: {msg}");
+                answers.warn(msg);
+            }
+        }
+    }
 
-    let start_names: Vec<&str> = nodes
-        .iter()
-        .filter(|n| n.name.ends_with('A'))
-        .map(|n| n.name.as_str())
+    let starts: Vec<usize> = (0..network.names.len())
+        .filter(|n| network.name(*n).ends_with('A'))
         .collect();
 
-    if start_names.contains(&"AAA") {
+    if let Some(path) = arg_value(args, "--export-dot") {
+        let dot_starts: Result<Vec<usize>, String> = match arg_value(args, "--dot-starts") {
+            Some(names) => names
+                .split(',')
+                .map(|n| network.index_of(n).ok_or(format!("Unknown start node {n}")))
+                .collect(),
+            None => Ok(starts.clone()),
+        };
+        let written = dot_starts.and_then(|dot_starts| {
+            let reachable_only = args.contains(&"--dot-reachable".to_owned());
+            let dot = to_dot(&network, &directions, &dot_starts, reachable_only);
+            std::fs::write(path, dot).map_err(|e| format!("Couldn't write {path}: {e}"))
+        });
+        match written {
+            Ok(()) => println!("Wrote the network to {path}"),
+            Err(msg) => {
+                println!("WARNING: {msg}");
+                answers.warn(msg);
+            }
+        }
+    }
+    let mut combined: Option<Arrivals> = None;
+    for start in starts.iter() {
+        let arrivals = Arrivals::walk(&network, &directions, *start, |n| {
+            network.name(n).ends_with('Z')
+        });
         println!(
-            "Number of steps (part 1): {}",
-            count_steps_to_z(&directions, &nodes, "AAA")
-                .get("ZZZ")
-                .unwrap()
+            "{}: ends at {:?}, then from step {} at {:?} every {} steps",
+            network.name(*start),
+            arrivals.finite,
+            arrivals.cycle_start,
+            arrivals.residues,
+            arrivals.period
         );
+        combined = match combined {
+            None => Some(arrivals),
+            Some(c) => match c.intersect(&arrivals) {
+                Ok(c) => Some(c),
+                Err(msg) => {
+                    println!("WARNING: {msg}");
+                    answers.warn(msg);
+                    return answers;
+                }
+            },
+        };
     }
 
-    let path_counts: Vec<HashMap<&str, usize>> = start_names
-        .iter()
-        .map(|n| count_steps_to_z(&directions, &nodes, n))
-        .collect();
-
-    for (start, map) in start_names.iter().zip(path_counts.iter()) {
-        println!("{}:", start);
-        for (k, v) in map.iter() {
-            println!("- {}: {}", k, v);
+    match combined.as_ref().and_then(|c| c.earliest()) {
+        Some(num_steps) => {
+            println!(
+                "Number of steps until all z's are reached (part 2): {}",
+                num_steps
+            );
+            answers.set(2, num_steps);
+        }
+        None => {
+            let msg = "The ghosts never all stand on end nodes at the same time";
+            println!("WARNING: {msg}");
+            answers.warn(msg);
         }
     }
+    answers
+}
 
-    // Assuming that each path has only one end
-    let counts: Vec<usize> = path_counts
-        .iter()
-        .map(|map| *map.values().next().unwrap())
-        .collect();
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use rstest::rstest;
 
-    let lcm = get_least_common_multiple(&counts);
-    println!(
-        "Number of steps until all z's are reached (part 2): {}",
-        lcm
-    );
+    fn nodes(lines: &[&str]) -> Vec<Node> {
+        lines.iter().map(|l| Node::from_str(l).unwrap()).collect()
+    }
+
+    #[rstest]
+    #[case("AAA = (BBB, CCC)", "AAA", "BBB", "CCC")]
+    #[case("11A = (11B, XXX)", "11A", "11B", "XXX")]
+    #[case("start = (a, somewhere_else)", "start", "a", "somewhere_else")]
+    fn test_parse_node(
+        #[case] line: &str,
+        #[case] name: &str,
+        #[case] left: &str,
+        #[case] right: &str,
+    ) {
+        let node = Node::from_str(line).unwrap();
+        assert_eq!(
+            (node.name.as_str(), node.left.as_str(), node.right.as_str()),
+            (name, left, right)
+        );
+    }
+
+    #[rstest]
+    #[case("AAA = (BBB CCC)")]
+    #[case("AAA = (BBB, CCC")]
+    #[case(" = (BBB, CCC)")]
+    fn test_parse_node_errors(#[case] line: &str) {
+        assert!(Node::from_str(line).is_err());
+    }
+
+    #[test]
+    fn test_network() {
+        let network = Network::from_nodes(&nodes(&[
+            "AAA = (BBBB, ZZZ)",
+            "BBBB = (AAA, ZZZ)",
+            "ZZZ = (ZZZ, ZZZ)",
+        ]))
+        .unwrap();
+        let start = network.index_of("AAA").unwrap();
+        let directions = [Direction::Left, Direction::Left, Direction::Right];
+        let end = network.index_of("ZZZ").unwrap();
+        let arrivals = Arrivals::walk(&network, &directions, start, |n| n == end);
+        assert_eq!(arrivals.earliest(), Some(3));
+    }
+
+    #[test]
+    fn test_network_errors() {
+        let errors = Network::from_nodes(&nodes(&[
+            "AAA = (BBB, CCC)",
+            "BBB = (AAA, AAA)",
+            "BBB = (AAA, DDD)",
+        ]))
+        .err()
+        .unwrap();
+        assert_eq!(
+            errors,
+            vec![
+                "Node BBB is declared twice",
+                "Node AAA leads to unknown node CCC",
+                "Node BBB leads to unknown node DDD",
+            ]
+        );
+    }
+
+    fn ghost_arrivals(lines: &[&str], directions: &str) -> Option<u64> {
+        let network = Network::from_nodes(&nodes(lines)).unwrap();
+        let directions: Vec<Direction> = directions
+            .chars()
+            .map(|c| {
+                if c == 'R' {
+                    Direction::Right
+                } else {
+                    Direction::Left
+                }
+            })
+            .collect();
+        (0..network.names.len())
+            .filter(|n| network.name(*n).ends_with('A'))
+            .map(|s| Arrivals::walk(&network, &directions, s, |n| network.name(n).ends_with('Z')))
+            .reduce(|a, b| a.intersect(&b).unwrap())
+            .and_then(|a| a.earliest())
+    }
+
+    #[test]
+    fn test_example_part_2() {
+        let lines = [
+            "11A = (11B, XXX)",
+            "11B = (XXX, 11Z)",
+            "11Z = (11B, XXX)",
+            "22A = (22B, XXX)",
+            "22B = (22C, 22C)",
+            "22C = (22Z, 22Z)",
+            "22Z = (22B, 22B)",
+            "XXX = (XXX, XXX)",
+        ];
+        assert_eq!(ghost_arrivals(&lines, "LR"), Some(6));
+    }
+
+    #[test]
+    fn test_ends_off_the_cycle_length() {
+        // 1A reaches 1Z after 2 steps and then every 3, 2A reaches 2Z after
+        // 1 step and then every 2, so they first line up at step 5
+        let lines = [
+            "1A = (1B, 1B)",
+            "1B = (1Z, 1Z)",
+            "1Z = (1C, 1C)",
+            "1C = (1B, 1B)",
+            "2A = (2Z, 2Z)",
+            "2Z = (2B, 2B)",
+            "2B = (2Z, 2Z)",
+        ];
+        assert_eq!(ghost_arrivals(&lines, "L"), Some(5));
+    }
+
+    #[test]
+    fn test_several_ends_and_prefix_hits() {
+        // 1A passes 1Z once before settling into a loop with two ends
+        let lines = [
+            "1A = (1Z, 1Z)",
+            "1Z = (1B, 1B)",
+            "1B = (1C, 1C)",
+            "1C = (1Y, 1Y)",
+            "1Y = (1D, 1D)",
+            "1D = (1E, 1E)",
+            "1E = (1X, 1X)",
+            "1X = (1B, 1B)",
+            "2A = (2B, 2B)",
+            "2B = (2Z, 2Z)",
+            "2Z = (2C, 2C)",
+            "2C = (2D, 2D)",
+            "2D = (2Z, 2Z)",
+        ];
+        // 1A ends at 1 and at 3, 6, 9, ...; 2A ends at 2, 5, 8, ...
+        assert_eq!(ghost_arrivals(&lines[..8], "L"), Some(1));
+        assert_eq!(ghost_arrivals(&lines[8..], "L"), Some(2));
+        assert_eq!(ghost_arrivals(&lines, "L"), None);
+    }
+
+    #[test]
+    fn test_instruction_index_is_part_of_the_state() {
+        // AAA comes round again at step 2, but on a different instruction
+        let lines = ["AAA = (BBB, BBB)", "BBB = (AAA, ZZZ)", "ZZZ = (ZZZ, ZZZ)"];
+        assert_eq!(ghost_arrivals(&lines, "RLL"), Some(4));
+        assert_eq!(ghost_arrivals(&lines, "LL"), None);
+    }
+
+    #[rstest]
+    #[case(2, 3, 1, 5, Some(11))]
+    #[case(1, 4, 3, 6, Some(9))]
+    #[case(1, 4, 2, 6, None)]
+    #[case(3, 4, 1, 6, Some(7))]
+    #[case(0, 1, 0, 1, Some(0))]
+    fn test_chinese_remainder(
+        #[case] a: u64,
+        #[case] m: u64,
+        #[case] b: u64,
+        #[case] n: u64,
+        #[case] expected: Option<u64>,
+    ) {
+        assert_eq!(chinese_remainder(a, m, b, n), expected);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let network = Network::from_nodes(&nodes(&[
+            "AAA = (BBB, ZZZ)",
+            "BBB = (ZZZ, ZZZ)",
+            "ZZZ = (ZZZ, ZZZ)",
+            "11A = (11Z, 11Z)",
+            "11Z = (11A, 11A)",
+        ]))
+        .unwrap();
+        let directions = [Direction::Left];
+        let start = network.index_of("AAA").unwrap();
+
+        let dot = to_dot(&network, &directions, &[start], false);
+        assert!(dot.contains("    \"AAA\" [shape=box, style=filled, fillcolor=palegreen];"));
+        assert!(dot.contains("    \"AAA\" -> \"BBB\" [label=L];"));
+        assert!(dot.contains("    \"ZZZ\" -> \"ZZZ\" [label=L, color=\"red\", penwidth=2];"));
+        assert!(dot.contains("    \"ZZZ\" -> \"ZZZ\" [label=R];"));
+        assert!(dot.contains("\"11Z\" [shape=doublecircle"));
+
+        let dot = to_dot(&network, &directions, &[start], true);
+        assert!(!dot.contains("11A"));
+        assert_eq!(dot.matches(" -> ").count(), 6);
+    }
 }
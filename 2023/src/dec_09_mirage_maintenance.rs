@@ -1,45 +1,181 @@
-use crate::load_file_lines;
+use crate::answers::Answers;
+use crate::{arg_value, load_file_lines};
 
-fn extrapolate_one(signal: &[i64]) -> (i64, i64) {
-    let mut hist: Vec<Vec<i64>> = Vec::with_capacity(signal.len());
-    hist.push(vec![0; signal.len()]);
-    hist[0][..signal.len()].clone_from_slice(signal);
-    for i in 1..signal.len() {
-        let mut curr_row: Vec<i64> = Vec::with_capacity(signal.len() - i);
-        let last_row = &hist[i - 1];
-        for j in 0..last_row.len() - 1 {
-            curr_row.push(last_row[j + 1] - last_row[j]);
-        }
-        if curr_row.iter().all(|x| *x == 0) {
-            break;
+pub const VERSION: u32 = 2;
+
+/// A sequence as the first entry of each of its difference rows, down to the
+/// constant one, which gives the value at any index `x` by Newton's forward
+/// difference formula: `sum(diffs[k] * C(x, k))`.
+#[derive(Debug, PartialEq)]
+struct Polynomial {
+    diffs: Vec<i128>,
+}
+
+impl Polynomial {
+    /// Fails if no difference row with at least two entries is constant, as
+    /// the sequence is then too short to pin down its polynomial.
+    pub fn fit(signal: &[i64]) -> Result<Self, String> {
+        let mut row: Vec<i128> = signal.iter().map(|v| *v as i128).collect();
+        let mut diffs = Vec::new();
+        while row.len() >= 2 {
+            diffs.push(row[0]);
+            if row.iter().all(|v| *v == row[0]) {
+                // A row of zeros adds nothing to the degree
+                if row[0] == 0 && diffs.len() > 1 {
+                    diffs.pop();
+                }
+                return Ok(Self { diffs });
+            }
+            row = row.windows(2).map(|w| w[1] - w[0]).collect();
         }
-        hist.push(curr_row);
+        Err(format!(
+            "The {} values never settle into a constant difference",
+            signal.len()
+        ))
     }
-    let mut before = hist.last().unwrap()[0];
-    for i in (0..hist.len() - 1).rev() {
-        before = hist[i][0] - before;
+
+    pub fn degree(&self) -> usize {
+        self.diffs.len() - 1
     }
-    let mut after = *hist.last().unwrap().last().unwrap();
-    for i in (0..hist.len() - 1).rev() {
-        after += *hist[i].last().unwrap();
+
+    /// The value at index `x`, where the sequence starts at 0. `x` can be
+    /// anywhere, including before the start.
+    pub fn value_at(&self, x: i128) -> Option<i128> {
+        let mut value: i128 = 0;
+        // C(x, k), which stays whole for whole x, even negative ones
+        let mut binomial: i128 = 1;
+        for (k, diff) in self.diffs.iter().enumerate() {
+            if k > 0 {
+                // C(x, k) = C(x, k - 1) * (x - k + 1) / k, with the division
+                // split up front so only the result has to fit
+                let k = k as i128;
+                let common = gcd(binomial, k);
+                let rest = (x - k + 1) / (k / common);
+                binomial = (binomial / common).checked_mul(rest)?;
+            }
+            value = value.checked_add(diff.checked_mul(binomial)?)?;
+        }
+        Some(value)
     }
-    (before, after)
 }
 
-pub fn run(args: &[String]) {
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+pub fn run(args: &[String]) -> Answers {
     let lines = load_file_lines(&args[0]).unwrap();
-    let dataset: Vec<Vec<i64>> = lines
-        .iter()
-        .map(|l| l.split(' ').filter_map(|n| n.parse().ok()).collect())
-        .collect();
-
-    let extrapolated_values: Vec<(i64, i64)> = dataset
-        .iter()
-        .map(|signal| extrapolate_one(signal))
-        .collect();
-    let checksum_1: i64 = extrapolated_values.iter().map(|(_, a)| *a).sum();
-    println!("Sum of extrapolated values after (part 1): {checksum_1}");
-
-    let checksum_2: i64 = extrapolated_values.iter().map(|(b, _)| *b).sum();
-    println!("Sum of extrapolated values before (part 2): {checksum_2}");
+    let steps: Option<Result<i128, String>> = arg_value(args, "--steps")
+        .map(|v| v.parse().map_err(|_| format!("Bad value for --steps: {v}")));
+
+    let mut answers = Answers::default();
+    let steps = match steps {
+        Some(Err(msg)) => {
+            answers.warn(msg);
+            None
+        }
+        Some(Ok(k)) => Some(k),
+        None => None,
+    };
+    let mut sequences: Vec<(usize, Polynomial)> = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate().filter(|(_, l)| !l.is_empty()) {
+        let signal: Vec<i64> = line.split(' ').filter_map(|n| n.parse().ok()).collect();
+        match Polynomial::fit(&signal) {
+            Ok(polynomial) => {
+                println!("Line {}: degree {}", i + 1, polynomial.degree());
+                sequences.push((signal.len(), polynomial));
+            }
+            Err(msg) => answers.warn(format!("Line {}: {msg}", i + 1)),
+        }
+    }
+    if let Some(degree) = sequences.iter().map(|(_, p)| p.degree()).max() {
+        println!("Highest degree: {degree}");
+    }
+
+    let sum_at = |index: &dyn Fn(usize) -> i128| -> Option<i128> {
+        sequences
+            .iter()
+            .map(|(len, p)| p.value_at(index(*len)))
+            .try_fold(0i128, |sum, v| sum.checked_add(v?))
+    };
+    let after = |k: i128| sum_at(&move |len| len as i128 - 1 + k);
+    let before = |k: i128| sum_at(&move |_| -k);
+
+    match after(1) {
+        Some(checksum_1) => {
+            println!("Sum of extrapolated values after (part 1): {checksum_1}");
+            answers.set(1, checksum_1);
+        }
+        None => answers.warn("The values after overflow"),
+    }
+    match before(1) {
+        Some(checksum_2) => {
+            println!("Sum of extrapolated values before (part 2): {checksum_2}");
+            answers.set(2, checksum_2);
+        }
+        None => answers.warn("The values before overflow"),
+    }
+    if let Some(k) = steps {
+        match (after(k), before(k)) {
+            (Some(a), Some(b)) => {
+                println!("Sum of values {k} steps after: {a}");
+                println!("Sum of values {k} steps before: {b}");
+            }
+            _ => answers.warn(format!("The values {k} steps out overflow")),
+        }
+    }
+
+    for msg in answers.diagnostics.iter() {
+        println!("WARNING: {msg}");
+    }
+    answers
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(&[0, 3, 6, 9, 12, 15], 1, 18, -3)]
+    #[case(&[1, 3, 6, 10, 15, 21], 2, 28, 0)]
+    #[case(&[10, 13, 16, 21, 30, 45], 3, 68, 5)]
+    #[case(&[7, 7, 7], 0, 7, 7)]
+    #[case(&[0, 0], 0, 0, 0)]
+    fn test_examples(
+        #[case] signal: &[i64],
+        #[case] degree: usize,
+        #[case] after: i128,
+        #[case] before: i128,
+    ) {
+        let polynomial = Polynomial::fit(signal).unwrap();
+        assert_eq!(polynomial.degree(), degree);
+        assert_eq!(polynomial.value_at(signal.len() as i128), Some(after));
+        assert_eq!(polynomial.value_at(-1), Some(before));
+    }
+
+    #[test]
+    fn test_far_horizons() {
+        // x^3 - 2x + 5, sampled at 0..6
+        let f = |x: i128| x * x * x - 2 * x + 5;
+        let signal: Vec<i64> = (0..6).map(|x| f(x) as i64).collect();
+        let polynomial = Polynomial::fit(&signal).unwrap();
+        assert_eq!(polynomial.degree(), 3);
+        for x in [-1_000_000, -7, 6, 100, 1_000_000_000_000, 5_000_000_000_000] {
+            assert_eq!(polynomial.value_at(x), Some(f(x)));
+        }
+        assert_eq!(polynomial.value_at(i128::MAX / 2), None);
+    }
+
+    #[rstest]
+    #[case(&[1, 2, 4, 8, 16])]
+    #[case(&[5])]
+    #[case(&[])]
+    fn test_never_constant(#[case] signal: &[i64]) {
+        assert!(Polynomial::fit(signal).is_err());
+    }
 }
@@ -1,12 +1,36 @@
-use crate::load_file_lines;
+use crate::answers::Answers;
+use crate::{arg_value, load_file_lines};
 
-use std::thread::sleep;
-use std::time::{Duration, Instant};
+use crate::visualize::{
+    fill_cells, render, restore_cells, solid, GridScale, Visualize, BACKGROUND,
+};
 
-use minifb::{Key, Window, WindowOptions};
-use raqote::{DrawOptions, DrawTarget, PathBuilder, SolidSource, Source};
+use raqote::DrawTarget;
 
-struct MazeNode {
+pub const VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    const ALL: [Self; 4] = [Self::Up, Self::Down, Self::Left, Self::Right];
+
+    fn opposite(self) -> Self {
+        match self {
+            Self::Up => Self::Down,
+            Self::Down => Self::Up,
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+        }
+    }
+}
+
+pub struct MazeNode {
     up_ok: bool,
     down_ok: bool,
     left_ok: bool,
@@ -14,14 +38,8 @@ struct MazeNode {
 }
 
 impl MazeNode {
-    pub fn from_char(c: char) -> Self {
-        match c {
-            'S' => Self {
-                up_ok: true,
-                down_ok: true,
-                left_ok: true,
-                right_ok: true,
-            },
+    pub fn from_char(c: char) -> Result<Self, String> {
+        let node = match c {
             '|' => Self {
                 up_ok: true,
                 down_ok: true,
@@ -64,31 +82,95 @@ impl MazeNode {
                 left_ok: false,
                 right_ok: false,
             },
-            _ => panic!("Bad character: {c}"),
+            _ => return Err(format!("Bad character: {c}")),
+        };
+        Ok(node)
+    }
+
+    fn from_connections(dirs: &[Direction]) -> Self {
+        Self {
+            up_ok: dirs.contains(&Direction::Up),
+            down_ok: dirs.contains(&Direction::Down),
+            left_ok: dirs.contains(&Direction::Left),
+            right_ok: dirs.contains(&Direction::Right),
+        }
+    }
+
+    fn num_connections(&self) -> usize {
+        Direction::ALL.iter().filter(|d| self.connects(**d)).count()
+    }
+
+    fn connects(&self, dir: Direction) -> bool {
+        match dir {
+            Direction::Up => self.up_ok,
+            Direction::Down => self.down_ok,
+            Direction::Left => self.left_ok,
+            Direction::Right => self.right_ok,
         }
     }
 }
 
-struct PipeMaze {
+pub struct PipeMaze {
     pub start: (usize, usize),
     pub nodes: Vec<Vec<MazeNode>>,
 }
 
 impl PipeMaze {
-    pub fn from_file_data(lines: &[String]) -> Self {
+    /// Parses the maze, working out the pipe under S from its neighbours
+    /// unless `start_pipe` says what it is.
+    pub fn from_file_data(lines: &[String], start_pipe: Option<char>) -> Result<Self, String> {
         let mut nodes: Vec<Vec<MazeNode>> = Vec::with_capacity(lines.len());
-        let mut start = (0, 0);
+        let mut start = None;
         for (i, line) in lines.iter().enumerate() {
             let mut row: Vec<MazeNode> = Vec::with_capacity(line.chars().count());
             for (j, c) in line.chars().enumerate() {
                 if c == 'S' {
-                    start = (j, i);
+                    if start.is_some() {
+                        return Err(format!("Second start S at row {} col {}", i + 1, j + 1));
+                    }
+                    start = Some((j, i));
+                    // Filled in once the neighbours are known
+                    row.push(MazeNode::from_connections(&[]));
+                    continue;
                 }
-                row.push(MazeNode::from_char(c));
+                row.push(MazeNode::from_char(c).map_err(|e| format!("Row {}: {e}", i + 1))?);
             }
             nodes.push(row);
         }
-        Self { start, nodes }
+        let start = start.ok_or("No start S in the maze")?;
+        let mut maze = Self { start, nodes };
+
+        let start_node = match start_pipe {
+            Some(c) => {
+                let node = MazeNode::from_char(c)?;
+                if node.num_connections() != 2 {
+                    return Err(format!("S can't be replaced with {c}, which isn't a pipe"));
+                }
+                node
+            }
+            None => maze.infer_start_node()?,
+        };
+        maze.nodes[start.1][start.0] = start_node;
+        Ok(maze)
+    }
+
+    /// S is whatever pipe joins the neighbours that point at it, as long as
+    /// exactly two do.
+    fn infer_start_node(&self) -> Result<MazeNode, String> {
+        let connected: Vec<Direction> = Direction::ALL
+            .into_iter()
+            .filter(|dir| {
+                self.neighbour(self.start, *dir)
+                    .is_some_and(|(col, row)| self.nodes[row][col].connects(dir.opposite()))
+            })
+            .collect();
+        if connected.len() != 2 {
+            return Err(format!(
+                "S has {} neighbouring pipes pointing at it instead of 2, use --start-pipe to say which pipe it is",
+                connected.len()
+            ));
+        }
+        Ok(MazeNode::from_connections(&connected))
     }
 
     pub fn num_rows(&self) -> usize {
@@ -98,9 +180,52 @@ impl PipeMaze {
     pub fn num_cols(&self) -> usize {
         self.nodes[0].len()
     }
+
+    fn neighbour(&self, coord: (usize, usize), dir: Direction) -> Option<(usize, usize)> {
+        let (col, row) = coord;
+        let next = match dir {
+            Direction::Up => (col, row.checked_sub(1)?),
+            Direction::Down => (col, row + 1),
+            Direction::Left => (col.checked_sub(1)?, row),
+            Direction::Right => (col + 1, row),
+        };
+        self.nodes.get(next.1)?.get(next.0)?;
+        Some(next)
+    }
+
+    /// The `(col, row)` of every tile of the loop through S, in order
+    /// starting at S. None if S's pipe doesn't lead back to it.
+    pub fn trace_loop(&self) -> Option<Vec<(usize, usize)>> {
+        let node_at = |(col, row): (usize, usize)| &self.nodes[row][col];
+        for first_dir in Direction::ALL {
+            let mut tiles = vec![self.start];
+            let mut coord = self.start;
+            let mut dir = first_dir;
+            while let Some(next) = self.neighbour(coord, dir) {
+                if !node_at(coord).connects(dir) || !node_at(next).connects(dir.opposite()) {
+                    break;
+                }
+                if next == self.start {
+                    return Some(tiles);
+                }
+                tiles.push(next);
+                coord = next;
+                // Every pipe has exactly two ends
+                let came_from = dir.opposite();
+                let Some(next_dir) = Direction::ALL
+                    .into_iter()
+                    .find(|d| *d != came_from && node_at(coord).connects(*d))
+                else {
+                    break;
+                };
+                dir = next_dir;
+            }
+        }
+        None
+    }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum NodeStatus {
     None,
     PipeNotVisited,
@@ -116,6 +241,8 @@ struct MazeNavigation {
     node_statuses: Vec<NodeStatus>,
     num_cols: usize,
     num_rows: usize,
+    // Every status change as (index, previous status), so steps can be undone
+    changes: Vec<(usize, NodeStatus)>,
 }
 
 impl MazeNavigation {
@@ -125,6 +252,7 @@ impl MazeNavigation {
             node_statuses: vec![NodeStatus::None; 9 * maze.num_rows() * maze.num_cols()],
             num_cols: 3 * maze.num_cols(),
             num_rows: 3 * maze.num_rows(),
+            changes: Vec::new(),
         };
 
         // TODO This can be initialized with data directly from the file
@@ -170,6 +298,26 @@ impl MazeNavigation {
         self.num_cols * row + col
     }
 
+    fn set_status(&mut self, idx: usize, status: NodeStatus) {
+        self.changes.push((idx, self.node_statuses[idx]));
+        self.node_statuses[idx] = status;
+    }
+
+    /// Undoes status changes until only the first `num_changes` are left.
+    fn undo_changes(&mut self, num_changes: usize) {
+        while self.changes.len() > num_changes {
+            let (idx, status) = self.changes.pop().unwrap();
+            self.node_statuses[idx] = status;
+        }
+    }
+
+    fn status_at(&self, coord: &(usize, usize)) -> Option<NodeStatus> {
+        if coord.0 >= self.num_cols || coord.1 >= self.num_rows {
+            return None;
+        }
+        Some(self.node_statuses[self.to_flat_idx(coord)])
+    }
+
     fn get_adjacent_matching(
         &self,
         coord: &(usize, usize),
@@ -218,14 +366,15 @@ impl MazeNavigation {
 
     pub fn advance_maze_nav(&mut self) -> usize {
         let mut nodes_advanced = 0;
-        let mut next_active_nodes = Vec::with_capacity(self.active_nodes.capacity());
-        for coord in self.active_nodes.iter() {
+        let active_nodes = std::mem::take(&mut self.active_nodes);
+        let mut next_active_nodes = Vec::with_capacity(active_nodes.capacity());
+        for coord in active_nodes.iter() {
             let pathable_coords =
                 self.get_adjacent_matching(coord, NodeStatus::PipeNotVisited, false);
             for pc in pathable_coords.iter() {
                 let idx = self.to_flat_idx(pc);
                 next_active_nodes.push(*pc);
-                self.node_statuses[idx] = NodeStatus::PipeVisited;
+                self.set_status(idx, NodeStatus::PipeVisited);
                 nodes_advanced += 1;
             }
         }
@@ -281,13 +430,14 @@ impl MazeNavigation {
             }
         }
         let mut nodes_advanced = 0;
-        let mut next_active_nodes = Vec::with_capacity(self.active_nodes.capacity());
-        for coord in self.active_nodes.iter() {
+        let active_nodes = std::mem::take(&mut self.active_nodes);
+        let mut next_active_nodes = Vec::with_capacity(active_nodes.capacity());
+        for coord in active_nodes.iter() {
             let pathable_coords = self.get_adjacent_matching(coord, NodeStatus::None, true);
             for pc in pathable_coords.iter() {
                 next_active_nodes.push(*pc);
                 let idx = self.to_flat_idx(pc);
-                self.node_statuses[idx] = NodeStatus::EmptyVisited;
+                self.set_status(idx, NodeStatus::EmptyVisited);
                 nodes_advanced += 1;
             }
         }
@@ -343,180 +493,511 @@ impl MazeNavigation {
     }
 }
 
-fn draw_node_status(
-    dt: &mut DrawTarget,
-    nav: &MazeNavigation,
-    node_status: NodeStatus,
-    red: u8,
-    green: u8,
-    blue: u8,
-) {
-    let num_x = nav.num_cols as f32;
-    let num_y = nav.num_rows as f32;
-    let grid_width = dt.width() as f32 / num_x;
-    let grid_height = dt.height() as f32 / num_y;
-
-    let mut pb = PathBuilder::new();
-    for (idx, status) in nav.node_statuses.iter().enumerate() {
-        if *status == node_status {
-            let row = idx / nav.num_cols;
-            let col = idx % nav.num_cols;
-            let x = grid_width * col as f32;
-            let y = grid_height * row as f32;
-            pb.rect(x, y, grid_width, grid_height);
-        }
-    }
-    let path = pb.finish();
-    dt.fill(
-        &path,
-        &Source::Solid(SolidSource::from_unpremultiplied_argb(
-            0xa0, red, green, blue,
-        )),
-        &DrawOptions::new(),
+const PIPE_COLOR: (u8, u8, u8) = (0x00, 0x00, 0xff);
+
+// Colors of the visited cells, drawn over the pipes in this order
+const STATUS_COLORS: [(NodeStatus, (u8, u8, u8)); 2] = [
+    (NodeStatus::PipeVisited, (0x00, 0xff, 0x00)),
+    (NodeStatus::EmptyVisited, (0x00, 0xff, 0xff)),
+];
+
+/// Draws the background and the pipes, which stay the same all search long.
+fn draw_pipes(dt: &mut DrawTarget, nav: &MazeNavigation) {
+    dt.clear(BACKGROUND);
+    let scale = GridScale::new(dt, nav.num_cols, nav.num_rows);
+    let pipes = (0..nav.node_statuses.len())
+        .filter(|idx| {
+            matches!(
+                nav.node_statuses[*idx],
+                NodeStatus::PipeNotVisited | NodeStatus::PipeVisited
+            )
+        })
+        .map(|idx| (idx % nav.num_cols, idx / nav.num_cols));
+    let (red, green, blue) = PIPE_COLOR;
+    fill_cells(dt, &scale, pipes, &solid(0xa0, red, green, blue));
+}
+
+/// Draws the search over the given cells of the pipes, active nodes on top.
+fn draw_cells(dt: &mut DrawTarget, nav: &MazeNavigation, cells: &[(usize, usize)]) {
+    let scale = GridScale::new(dt, nav.num_cols, nav.num_rows);
+    for (node_status, (red, green, blue)) in STATUS_COLORS {
+        let matching = cells
+            .iter()
+            .filter(|coord| nav.node_statuses[nav.to_flat_idx(coord)] == node_status)
+            .cloned();
+        fill_cells(dt, &scale, matching, &solid(0xa0, red, green, blue));
+    }
+    fill_cells(
+        dt,
+        &scale,
+        nav.active_nodes.iter().cloned(),
+        &solid(0xff, 0xff, 0x00, 0x00),
     );
 }
 
 fn draw_navigation(dt: &mut DrawTarget, nav: &MazeNavigation) {
-    draw_node_status(dt, nav, NodeStatus::PipeNotVisited, 0x00, 0x00, 0xff);
-    draw_node_status(dt, nav, NodeStatus::PipeVisited, 0x00, 0xff, 0x00);
-    draw_node_status(dt, nav, NodeStatus::EmptyVisited, 0x00, 0xff, 0xff);
-
-    // Draw active nodes
-    let num_x = nav.num_cols as f32;
-    let num_y = nav.num_rows as f32;
-    let grid_width = dt.width() as f32 / num_x;
-    let grid_height = dt.height() as f32 / num_y;
-
-    let mut pb = PathBuilder::new();
-    for coord in nav.active_nodes.iter() {
-        let x = grid_width * coord.0 as f32;
-        let y = grid_height * coord.1 as f32;
-        pb.rect(x, y, grid_width, grid_height);
-    }
-    let path = pb.finish();
-    dt.fill(
-        &path,
-        &Source::Solid(SolidSource::from_unpremultiplied_argb(
-            0xff, 0xff, 0x00, 0x00,
-        )),
-        &DrawOptions::new(),
-    );
+    let all_cells: Vec<(usize, usize)> = (0..nav.node_statuses.len())
+        .map(|idx| (idx % nav.num_cols, idx / nav.num_cols))
+        .collect();
+    draw_cells(dt, nav, &all_cells);
 }
 
-#[derive(Debug, Default)]
-struct PerformanceTimer
-{
-    pub clear: u128,
-    pub p1_advance: u128,
-    pub p1_draw: u128,
-    pub p2_advance: u128,
-    pub p2_draw: u128,
-    pub win_update: u128,
+/// What was last drawn by `redraw`, so the next one only paints changes.
+struct DrawnFrame {
+    num_changes: usize,
+    active_nodes: Vec<(usize, usize)>,
+    /// Cells that rewinding has changed back since
+    undone: Vec<(usize, usize)>,
+    /// `draw_pipes` on a target the size of the one drawn to, to restore
+    /// changed cells from
+    pipes: DrawTarget,
 }
 
-pub fn run(args: &[String]) {
-    let lines = load_file_lines(&args[0]).unwrap();
-    let maze_refresh_ms: u64 = args[1].parse().unwrap();
-    let pipe_maze = PipeMaze::from_file_data(&lines);
-    let mut navigation = MazeNavigation::new(&pipe_maze);
+/// Walks the loop for part 1, then floods the outside for part 2.
+struct MazeSearch {
+    nav: MazeNavigation,
+    maze_steps_taken: usize,
+    loop_len: Option<usize>,
+    num_enclosed: Option<usize>,
+}
 
-    let do_print_time = args.contains(&"-t".to_owned()) || args.contains(&"--time".to_owned());
+impl MazeSearch {
+    pub fn new(maze: &PipeMaze) -> Self {
+        Self {
+            nav: MazeNavigation::new(maze),
+            maze_steps_taken: 0,
+            loop_len: None,
+            num_enclosed: None,
+        }
+    }
 
-    let default_window_refresh_ms = 20;
-    let window_refresh_ms = if maze_refresh_ms < default_window_refresh_ms {
-        maze_refresh_ms
-    } else {
-        default_window_refresh_ms
-    };
-    let window_refresh_us = 1000 * window_refresh_ms;
-    let width = 750;
-    let height = 750;
-    let mut window = Window::new(
-        "AoC 2023 - Day 10 - Pipe Maze",
-        width,
-        height,
-        WindowOptions::default(),
-    )
-    .unwrap_or_else(|e| {
-        panic!("{e}");
-    });
-
-    let (win_width, win_height) = window.get_size();
-    let mut dt = DrawTarget::new(win_width as i32, win_height as i32);
-
-    draw_navigation(&mut dt, &navigation);
-    window
-        .update_with_buffer(dt.get_data(), win_width, win_height)
-        .unwrap();
-    sleep(Duration::from_millis(window_refresh_ms));
-
-    let mut iter = 1;
-    let mut maze_steps_taken = 0;
-    let mut part_1_solved = false;
-    let mut part_2_solved = false;
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        let iter_start = Instant::now();
-        let mut perf_timer = PerformanceTimer::default();
-
-        let should_refresh =
-            !(part_1_solved && part_2_solved) && iter % (maze_refresh_ms / window_refresh_ms) == 0;
-
-        if should_refresh {
-            let start_clear = Instant::now();
-            dt.clear(SolidSource::from_unpremultiplied_argb(
-                0x00, 0x00, 0x00, 0x00,
-            ));
-            perf_timer.clear = start_clear.elapsed().as_micros();
-
-            if !part_1_solved {
-                let start_nav = Instant::now();
-                let num_updated = navigation.advance_maze_nav();
-                perf_timer.p1_advance = start_nav.elapsed().as_micros();
-                if num_updated == 0 {
-                    println!("Steps in longest loop (part 1): {}", (maze_steps_taken / 3));
-                    part_1_solved = true;
-                    navigation.reset_active_nodes();
-                } else {
-                    maze_steps_taken += 1;
-                }
-                let start_draw = Instant::now();
-                draw_navigation(&mut dt, &navigation);
-                perf_timer.p1_draw = start_draw.elapsed().as_micros();
+    /// Takes one step of the search. Returns false once both answers are known.
+    pub fn step(&mut self) -> bool {
+        if self.loop_len.is_none() {
+            let num_updated = self.nav.advance_maze_nav();
+            if num_updated == 0 {
+                self.loop_len = Some(self.maze_steps_taken / 3);
+                self.nav.reset_active_nodes();
+            } else {
+                self.maze_steps_taken += 1;
             }
-            if part_1_solved && !part_2_solved {
-                let start_nav = Instant::now();
-                if navigation.advance_outer_nav().is_none() {
-                    let num_enclosed = navigation.num_maze_nodes()
-                        - navigation.count_traversed_maze_nodes()
-                        - navigation.count_empty_outer_nodes();
-                    println!("Spaces enclosed (part 2): {}", num_enclosed);
-                    part_2_solved = true;
-                }
-                perf_timer.p2_advance = start_nav.elapsed().as_micros();
-                let start_draw = Instant::now();
-                draw_navigation(&mut dt, &navigation);
-                perf_timer.p2_draw = start_draw.elapsed().as_micros();
+        }
+        if self.loop_len.is_some()
+            && self.num_enclosed.is_none()
+            && self.nav.advance_outer_nav().is_none()
+        {
+            self.num_enclosed = Some(
+                self.nav.num_maze_nodes()
+                    - self.nav.count_traversed_maze_nodes()
+                    - self.nav.count_empty_outer_nodes(),
+            );
+        }
+        self.num_enclosed.is_none()
+    }
+}
+
+/// Runs the same search as the animation to completion, without drawing.
+/// Returns the steps to the farthest point of the loop and the number of
+/// tiles it encloses.
+pub fn solve(maze: &PipeMaze) -> (usize, usize) {
+    let mut search = MazeSearch::new(maze);
+    while search.step() {}
+    (search.loop_len.unwrap(), search.num_enclosed.unwrap())
+}
+
+/// Solves without searching, to check `solve`: the shoelace formula gives
+/// the area inside the loop and Pick's theorem turns that into a tile count.
+pub fn solve_by_area(maze: &PipeMaze) -> Option<(usize, usize)> {
+    let tiles = maze.trace_loop()?;
+    let twice_area: i64 = tiles
+        .iter()
+        .zip(tiles.iter().cycle().skip(1))
+        .map(|(a, b)| a.0 as i64 * b.1 as i64 - b.0 as i64 * a.1 as i64)
+        .sum::<i64>()
+        .abs();
+    // Pick's theorem, A = i + b/2 - 1, with the loop tiles as the b boundary points
+    let num_boundary = tiles.len() as i64;
+    let num_interior = (twice_area - num_boundary) / 2 + 1;
+    Some((tiles.len() / 2, num_interior as usize))
+}
+
+/// What's needed to step the animation back to an earlier frame.
+struct AnimationFrame {
+    num_changes: usize,
+    active_nodes: Vec<(usize, usize)>,
+    maze_steps_taken: usize,
+    loop_len: Option<usize>,
+    num_enclosed: Option<usize>,
+}
+
+struct PipeMazeAnimation {
+    search: MazeSearch,
+    history: Vec<AnimationFrame>,
+    drawn: Option<DrawnFrame>,
+    answers: Answers,
+}
+
+impl PipeMazeAnimation {
+    pub fn new(maze: &PipeMaze) -> Self {
+        Self {
+            search: MazeSearch::new(maze),
+            history: Vec::new(),
+            drawn: None,
+            answers: Answers::default(),
+        }
+    }
+
+    fn save_frame(&mut self) {
+        self.history.push(AnimationFrame {
+            num_changes: self.search.nav.changes.len(),
+            active_nodes: self.search.nav.active_nodes.clone(),
+            maze_steps_taken: self.search.maze_steps_taken,
+            loop_len: self.search.loop_len,
+            num_enclosed: self.search.num_enclosed,
+        });
+    }
+}
+
+impl Visualize for PipeMazeAnimation {
+    fn title(&self) -> String {
+        "AoC 2023 - Day 10 - Pipe Maze".to_owned()
+    }
+
+    fn advance(&mut self) -> bool {
+        self.save_frame();
+        let running = self.search.step();
+        // Stepping again after a rewind shouldn't print the answers twice
+        if let (Some(loop_len), None) = (self.search.loop_len, self.answers.get(1)) {
+            println!("Steps in longest loop (part 1): {}", loop_len);
+            self.answers.set(1, loop_len);
+        }
+        if let (Some(num_enclosed), None) = (self.search.num_enclosed, self.answers.get(2)) {
+            println!("Spaces enclosed (part 2): {}", num_enclosed);
+            self.answers.set(2, num_enclosed);
+        }
+        running
+    }
+
+    fn draw(&self, dt: &mut DrawTarget) {
+        draw_pipes(dt, &self.search.nav);
+        draw_navigation(dt, &self.search.nav);
+    }
+
+    fn redraw(&mut self, dt: &mut DrawTarget) {
+        let nav = &self.search.nav;
+        let same_size = |drawn: &DrawnFrame| {
+            drawn.pipes.width() == dt.width() && drawn.pipes.height() == dt.height()
+        };
+        let pipes = match self.drawn.take().filter(same_size) {
+            Some(drawn) => {
+                // Cells that changed status either way, plus where the active
+                // nodes were and are
+                let mut dirty = drawn.undone;
+                dirty.extend(
+                    nav.changes[drawn.num_changes..]
+                        .iter()
+                        .map(|(idx, _)| (idx % nav.num_cols, idx / nav.num_cols)),
+                );
+                dirty.extend(drawn.active_nodes.iter().cloned());
+                dirty.extend(nav.active_nodes.iter().cloned());
+                dirty.sort_unstable();
+                dirty.dedup();
+                let scale = GridScale::new(dt, nav.num_cols, nav.num_rows);
+                restore_cells(dt, &drawn.pipes, &scale, dirty.iter().cloned());
+                draw_cells(dt, nav, &dirty);
+                drawn.pipes
             }
+            None => {
+                let mut pipes = DrawTarget::new(dt.width(), dt.height());
+                draw_pipes(&mut pipes, nav);
+                dt.get_data_mut().copy_from_slice(pipes.get_data());
+                draw_navigation(dt, nav);
+                pipes
+            }
+        };
+        self.drawn = Some(DrawnFrame {
+            num_changes: nav.changes.len(),
+            active_nodes: nav.active_nodes.clone(),
+            undone: Vec::new(),
+            pipes,
+        });
+    }
 
-            let start_update = Instant::now();
-            window
-                .update_with_buffer(dt.get_data(), win_width, win_height)
-                .unwrap();
-            perf_timer.win_update = start_update.elapsed().as_micros();
+    fn rewind(&mut self) -> bool {
+        let Some(frame) = self.history.pop() else {
+            return false;
+        };
+        let nav = &mut self.search.nav;
+        if let Some(drawn) = &mut self.drawn {
+            drawn.undone.extend(
+                nav.changes[frame.num_changes..]
+                    .iter()
+                    .map(|(idx, _)| (idx % nav.num_cols, idx / nav.num_cols)),
+            );
+            drawn.num_changes = drawn.num_changes.min(frame.num_changes);
         }
+        nav.undo_changes(frame.num_changes);
+        nav.active_nodes = frame.active_nodes;
+        self.search.maze_steps_taken = frame.maze_steps_taken;
+        self.search.loop_len = frame.loop_len;
+        self.search.num_enclosed = frame.num_enclosed;
+        true
+    }
+
+    fn inspect(&self, dt: &DrawTarget, x: f32, y: f32) -> Option<String> {
+        let scale = GridScale::new(dt, self.search.nav.num_cols, self.search.nav.num_rows);
+        let coord = scale.cell_at(x, y);
+        let status = self.search.nav.status_at(&coord)?;
+        Some(format!(
+            "MAZE COL {} ROW {} - {:?}",
+            coord.0 / 3,
+            coord.1 / 3,
+            status
+        ))
+    }
 
-        if do_print_time {
-            print!("\r{:?}", perf_timer);
+    fn overlay(&self) -> Vec<String> {
+        let part_2 = match self.search.num_enclosed {
+            Some(num_enclosed) => num_enclosed.to_string(),
+            None => "-".to_owned(),
+        };
+        vec![
+            format!("PART 1: {}", self.search.maze_steps_taken / 3),
+            format!("PART 2: {}", part_2),
+        ]
+    }
+}
+
+fn solve_headless(maze: &PipeMaze) -> Answers {
+    let (loop_len, num_enclosed) = solve(maze);
+    println!("Steps in longest loop (part 1): {}", loop_len);
+    println!("Spaces enclosed (part 2): {}", num_enclosed);
+    let mut answers = Answers::new(loop_len, num_enclosed);
+    match solve_by_area(maze) {
+        Some(check) if check == (loop_len, num_enclosed) => {}
+        Some((check_len, check_enclosed)) => answers.warn(format!(
+            "Shoelace cross-check disagrees: {check_len} steps, {check_enclosed} enclosed"
+        )),
+        None => answers.warn("No loop through S to cross-check with"),
+    }
+    for msg in answers.diagnostics.iter() {
+        println!("WARNING: {msg}");
+    }
+    answers
+}
+
+/// Only headless runs are cached, as any other run is for the animation.
+pub fn cached_parts(args: &[String]) -> Option<Vec<usize>> {
+    let headless = args.chunks(2).any(|c| c == ["--render", "none"]);
+    let answers_only = args
+        .chunks(2)
+        .all(|c| c == ["--render", "none"] || (c.len() == 2 && c[0] == "--start-pipe"));
+    (headless && answers_only).then(|| vec![1, 2])
+}
+
+pub fn run(args: &[String]) -> Answers {
+    let lines = load_file_lines(&args[0]).unwrap();
+    let start_pipe = arg_value(args, "--start-pipe").and_then(|v| v.chars().next());
+    let pipe_maze = match PipeMaze::from_file_data(&lines, start_pipe) {
+        Ok(maze) => maze,
+        Err(msg) => {
+            println!("WARNING: {msg}");
+            let mut answers = Answers::default();
+            answers.warn(msg);
+            return answers;
         }
+    };
+    if args
+        .windows(2)
+        .any(|w| w[0] == "--render" && w[1] == "none")
+    {
+        return solve_headless(&pipe_maze);
+    }
+    let mut animation = PipeMazeAnimation::new(&pipe_maze);
+
+    let frame_period_ms = args.get(1).and_then(|a| a.parse().ok()).unwrap_or(20);
+    if let Err(msg) = render(&mut animation, args, frame_period_ms) {
+        animation.answers.warn(msg);
+    }
+    for msg in animation.answers.diagnostics.iter() {
+        println!("WARNING: {msg}");
+    }
+    animation.answers
+}
 
-        let iter_duration = iter_start.elapsed().as_micros() as u64;
-        let wait_time_us = if iter_duration < window_refresh_us {
-            window_refresh_us - iter_duration
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use rstest::rstest;
+
+    fn maze_from(lines: &[&str]) -> PipeMaze {
+        let lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+        PipeMaze::from_file_data(&lines, None).unwrap()
+    }
+
+    fn example_maze() -> PipeMaze {
+        maze_from(&["-L|F7", "7S-7|", "L|7||", "-L-J|", "L|-JF"])
+    }
+
+    #[rstest]
+    #[case(&["-L|F7", "7S-7|", "L|7||", "-L-J|", "L|-JF"], 4, 1)]
+    #[case(&["..F7.", ".FJ|.", "SJ.L7", "|F--J", "LJ..."], 8, 1)]
+    #[case(
+        &[
+            "...........",
+            ".S-------7.",
+            ".|F-----7|.",
+            ".||.....||.",
+            ".||.....||.",
+            ".|L-7.F-J|.",
+            ".|..|.|..|.",
+            ".L--J.L--J.",
+            "...........",
+        ],
+        23,
+        4
+    )]
+    #[case(
+        &[
+            "..........",
+            ".S------7.",
+            ".|F----7|.",
+            ".||....||.",
+            ".||....||.",
+            ".|L-7F-J|.",
+            ".|..||..|.",
+            ".L--JL--J.",
+            "..........",
+        ],
+        22,
+        4
+    )]
+    #[case(
+        &[
+            ".F----7F7F7F7F-7....",
+            ".|F--7||||||||FJ....",
+            ".||.FJ||||||||L7....",
+            "FJL7L7LJLJ||LJ.L-7..",
+            "L--J.L7...LJS7F-7L7.",
+            "....F-J..F7FJ|L7L7L7",
+            "....L7.F7||L7|.L7L7|",
+            ".....|FJLJ|FJ|F7|.LJ",
+            "....FJL-7.||.||||...",
+            "....L---J.LJ.LJLJ...",
+        ],
+        70,
+        8
+    )]
+    #[case(
+        &[
+            "FF7FSF7F7F7F7F7F---7",
+            "L|LJ||||||||||||F--J",
+            "FL-7LJLJ||||||LJL-77",
+            "F--JF--7||LJLJ7F7FJ-",
+            "L---JF-JLJ.||-FJLJJ7",
+            "|F|F-JF---7F7-L7L|7|",
+            "|FFJF7L7F-JF7|JL---7",
+            "7-L-JL7||F7|L7F-7F7|",
+            "L.L7LFJ|||||FJL7||LJ",
+            "L7JLJL-JLJLJL--JLJ.L",
+        ],
+        80,
+        10
+    )]
+    fn test_examples(#[case] lines: &[&str], #[case] loop_len: usize, #[case] num_enclosed: usize) {
+        let maze = maze_from(lines);
+        assert_eq!(solve(&maze), (loop_len, num_enclosed));
+        assert_eq!(solve_by_area(&maze), Some((loop_len, num_enclosed)));
+    }
+
+    #[test]
+    fn test_no_loop_to_trace() {
+        let maze = maze_from(&[".....", ".S-7.", ".|.|.", ".L-..", "....."]);
+        assert_eq!(maze.trace_loop(), None);
+    }
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_start_inferred() {
+        let maze = example_maze();
+        let start = &maze.nodes[maze.start.1][maze.start.0];
+        assert!(start.down_ok && start.right_ok && !start.up_ok && !start.left_ok);
+    }
+
+    #[rstest]
+    // The - left of S points at it without being part of the loop
+    #[case(&["-S-7.", ".|.|.", ".L-J."], None, None)]
+    #[case(&["-S-7.", ".|.|.", ".L-J."], Some('F'), Some((4, 1)))]
+    #[case(&[".|.", "-S-", ".|."], None, None)]
+    #[case(&["....", ".S-.", "...."], None, None)]
+    #[case(&["F-7", "|S|", "L-J"], Some('.'), None)]
+    #[case(&["F-7", "|.|", "L-J"], None, None)]
+    #[case(&["F-7", "|SS", "L-J"], None, None)]
+    #[case(&["F-7", "|S|", "L-X"], Some('|'), None)]
+    fn test_start_pipe(
+        #[case] maze_lines: &[&str],
+        #[case] start_pipe: Option<char>,
+        #[case] expected: Option<(usize, usize)>,
+    ) {
+        let maze = PipeMaze::from_file_data(&lines(maze_lines), start_pipe);
+        assert_eq!(maze.as_ref().ok().map(solve), expected);
+    }
+
+    #[test]
+    fn test_rewind_restores_frames() {
+        let maze = example_maze();
+        let mut animation = PipeMazeAnimation::new(&maze);
+        let initial_statuses = animation.search.nav.node_statuses.clone();
+        let mut num_frames = 0;
+        while animation.advance() {
+            num_frames += 1;
         }
-        else {
-            10
+        assert_eq!(animation.answers.get(1), Some("4"));
+        assert_eq!(animation.answers.get(2), Some("1"));
+
+        while animation.rewind() {
+            num_frames -= 1;
+        }
+        assert_eq!(num_frames, -1);
+        assert!(animation.search.nav.node_statuses == initial_statuses);
+        assert_eq!(animation.search.nav.active_nodes, vec![(4, 4)]);
+
+        // Replaying reaches the same answers
+        while animation.advance() {}
+        assert_eq!(animation.overlay(), vec!["PART 1: 4", "PART 2: 1"]);
+    }
+
+    #[test]
+    fn test_incremental_redraw_matches_full_draw() {
+        let maze = example_maze();
+        let mut animation = PipeMazeAnimation::new(&maze);
+        // Cells aren't a whole number of pixels, so rounding has to agree too
+        let mut incremental = DrawTarget::new(37, 41);
+        let mut full = DrawTarget::new(37, 41);
+        let mut check_frame = |animation: &mut PipeMazeAnimation| {
+            animation.redraw(&mut incremental);
+            full.clear(BACKGROUND);
+            animation.draw(&mut full);
+            assert!(incremental.get_data() == full.get_data());
         };
-        sleep(Duration::from_micros(wait_time_us));
-        iter += 1;
+
+        check_frame(&mut animation);
+        while animation.advance() {
+            check_frame(&mut animation);
+        }
+        check_frame(&mut animation);
+        for _ in 0..5 {
+            animation.rewind();
+            check_frame(&mut animation);
+        }
+        // Rewinding repaints just the undone cells
+        animation.rewind();
+        assert!(animation.drawn.is_some());
+        check_frame(&mut animation);
+        animation.advance();
+        check_frame(&mut animation);
     }
 }
@@ -1,14 +1,14 @@
+use crate::answers::Answers;
 use crate::load_file_lines;
 
-// TODO Solve the off by 1 error. Problem works if given 9, 99, etc, not 10, 100.
+pub const VERSION: u32 = 2;
 
 struct StarMap {
     coords: Vec<(usize, usize)>,
-    exp_factor: usize,
 }
 
 impl StarMap {
-    pub fn from_file_data(file_data: &[String], exp_factor: usize) -> Self {
+    pub fn from_file_data(file_data: &[String]) -> Self {
         let mut coords = Vec::new();
         for (row, line) in file_data.iter().enumerate() {
             for (col, c) in line.chars().enumerate() {
@@ -17,64 +17,145 @@ impl StarMap {
                 }
             }
         }
-        Self { coords, exp_factor }
+        Self { coords }
     }
 
-    fn expand_cols(&mut self) {
-        self.coords.sort_by(|a, b| a.0.cmp(&b.0));
-        for i in 1..self.coords.len() {
-            if self.coords[i - 1].0 + 1 < self.coords[i].0 {
-                let diff = self.coords[i].0 - self.coords[i - 1].0 - 1;
-                for j in i..self.coords.len() {
-                    self.coords[j].0 += self.exp_factor * diff;
-                }
-            }
-        }
+    /// The galaxies once every empty row and column has become `factor` of
+    /// them.
+    pub fn expanded(&self, factor: usize) -> Vec<(usize, usize)> {
+        let cols = expand_axis(&self.coords.iter().map(|c| c.0).collect::<Vec<_>>(), factor);
+        let rows = expand_axis(&self.coords.iter().map(|c| c.1).collect::<Vec<_>>(), factor);
+        cols.into_iter().zip(rows).collect()
     }
 
-    fn expand_rows(&mut self) {
-        self.coords.sort_by(|a, b| a.1.cmp(&b.1));
-        for i in 1..self.coords.len() {
-            if self.coords[i - 1].1 + 1 < self.coords[i].1 {
-                let diff = self.coords[i].1 - self.coords[i - 1].1 - 1;
-                for j in i..self.coords.len() {
-                    self.coords[j].1 += self.exp_factor * diff;
-                }
-            }
-        }
+    pub fn sum_distances(&self, factor: usize) -> usize {
+        let coords = self.expanded(factor);
+        sum_gaps(coords.iter().map(|c| c.0).collect())
+            + sum_gaps(coords.iter().map(|c| c.1).collect())
+    }
+}
+
+/// Moves each position along by `factor - 1` for every empty line before it.
+fn expand_axis(positions: &[usize], factor: usize) -> Vec<usize> {
+    let mut occupied = positions.to_vec();
+    occupied.sort();
+    occupied.dedup();
+    positions
+        .iter()
+        .map(|p| {
+            let empty_before = p - occupied.partition_point(|o| o < p);
+            p + (factor - 1) * empty_before
+        })
+        .collect()
+}
+
+/// Sums the distances between every pair of positions on one axis. Once
+/// sorted, the `i`th position is that far past each of the `i` before it.
+fn sum_gaps(mut positions: Vec<usize>) -> usize {
+    positions.sort();
+    let mut before = 0;
+    let mut total = 0;
+    for (i, p) in positions.iter().enumerate() {
+        total += p * i - before;
+        before += p;
     }
+    total
+}
 
-    pub fn expand(&mut self) {
-        self.expand_cols();
-        self.expand_rows();
+/// Part 1 expands by 2 and part 2 by a million. Other factors are only
+/// printed, so those runs aren't cached.
+pub fn cached_parts(args: &[String]) -> Option<Vec<usize>> {
+    if args.is_empty() {
+        return Some(vec![1, 2]);
     }
+    args.iter()
+        .map(|a| match a.as_str() {
+            "2" => Some(1),
+            "1000000" => Some(2),
+            _ => None,
+        })
+        .collect()
+}
 
-    pub fn sum_distances(&self) -> usize {
-        let mut total = 0;
-        for (i, a) in self.coords.iter().enumerate() {
-            for b in self.coords.iter().skip(i + 1) {
-                total += calc_dist(a, b);
+pub fn run(args: &[String]) -> Answers {
+    let lines = load_file_lines(&args[0]).unwrap();
+    let mut answers = Answers::default();
+    let mut factors = Vec::new();
+    for arg in args.iter().skip(1) {
+        match arg.parse::<usize>() {
+            Ok(factor) if factor > 0 => factors.push(factor),
+            _ => {
+                let msg = format!("Bad expansion factor: {arg}");
+                println!("WARNING: {msg}");
+                answers.warn(msg);
             }
         }
-        total
     }
-}
+    if factors.is_empty() {
+        factors = vec![2, 1_000_000];
+    }
 
-fn calc_dist(a: &(usize, usize), b: &(usize, usize)) -> usize {
-    let dist_col = if a.0 > b.0 { a.0 - b.0 } else { b.0 - a.0 };
-    let dist_row = if a.1 > b.1 { a.1 - b.1 } else { b.1 - a.1 };
-    dist_col + dist_row
+    let galaxy_map = StarMap::from_file_data(&lines);
+    for factor in factors {
+        let total_distance = galaxy_map.sum_distances(factor);
+        println!("Distance after expanding {factor} times: {total_distance}");
+        match factor {
+            2 => answers.set(1, total_distance),
+            1_000_000 => answers.set(2, total_distance),
+            _ => (),
+        }
+    }
+    answers
 }
 
-pub fn run(args: &[String]) {
-    let lines = load_file_lines(&args[0]).unwrap();
-    let num_iter: usize = args[1].parse().unwrap_or(1);
-    let mut galaxy_map = StarMap::from_file_data(&lines, num_iter);
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use rstest::rstest;
 
-    galaxy_map.expand();
+    fn example() -> StarMap {
+        let lines: Vec<String> = [
+            "...#......",
+            ".......#..",
+            "#.........",
+            "..........",
+            "......#...",
+            ".#........",
+            ".........#",
+            "..........",
+            ".......#..",
+            "#...#.....",
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+        StarMap::from_file_data(&lines)
+    }
 
-    println!(
-        "Distance after expansion: {}",
-        galaxy_map.sum_distances()
-    );
+    #[rstest]
+    #[case(1, 292)]
+    #[case(2, 374)]
+    #[case(10, 1030)]
+    #[case(100, 8410)]
+    fn test_example(#[case] factor: usize, #[case] expected: usize) {
+        assert_eq!(example().sum_distances(factor), expected);
+    }
+
+    #[test]
+    fn test_matches_pairwise_sum() {
+        let coords = example().expanded(7);
+        let mut brute_force = 0;
+        for (i, a) in coords.iter().enumerate() {
+            for b in coords.iter().skip(i + 1) {
+                brute_force += a.0.abs_diff(b.0) + a.1.abs_diff(b.1);
+            }
+        }
+        assert_eq!(example().sum_distances(7), brute_force);
+    }
+
+    #[test]
+    fn test_expand_axis() {
+        // 1 and 2 are empty before 3, 4 before 5
+        assert_eq!(expand_axis(&[3, 0, 5, 3], 10), vec![21, 0, 32, 21]);
+    }
 }
@@ -0,0 +1,199 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::answer_cache::AnswerCache;
+use crate::aoc_client::AocClient;
+
+const DEFAULT_INPUTS_DIR: &str = "inputs";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FetchOutcome {
+    AlreadyPresent,
+    FromCache,
+    Downloaded,
+}
+
+/// Inputs go in `inputs/<year>/day-<dd>.txt` unless `AOC_INPUTS_DIR` says otherwise.
+pub fn default_inputs_dir() -> PathBuf {
+    env::var("AOC_INPUTS_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_INPUTS_DIR))
+}
+
+pub fn input_path(inputs_dir: &Path, year: usize, day: usize) -> PathBuf {
+    inputs_dir
+        .join(year.to_string())
+        .join(format!("day-{day:02}.txt"))
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    }
+    fs::write(path, contents).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+/// Puts the puzzle input in the inputs directory. An existing input is never
+/// replaced unless `force` is set, and a previously downloaded copy in the
+/// cache is reused before going to the network. The client is only built for
+/// a download, so the other cases work without a session.
+pub fn fetch_input(
+    client: impl FnOnce() -> Result<AocClient, String>,
+    year: usize,
+    day: usize,
+    inputs_dir: &Path,
+    cache_dir: &Path,
+    force: bool,
+) -> Result<(PathBuf, FetchOutcome), String> {
+    if !(1..=25).contains(&day) {
+        return Err(format!("There is no day {day}"));
+    }
+    let dest = input_path(inputs_dir, year, day);
+    if dest.exists() && !force {
+        return Ok((dest, FetchOutcome::AlreadyPresent));
+    }
+
+    let cached = input_path(&cache_dir.join("inputs"), year, day);
+    if cached.exists() && !force {
+        let contents = fs::read_to_string(&cached)
+            .map_err(|e| format!("Failed to read {}: {e}", cached.display()))?;
+        write_file(&dest, &contents)?;
+        return Ok((dest, FetchOutcome::FromCache));
+    }
+
+    let contents = client()?.get(&format!("/{year}/day/{day}/input"))?;
+    write_file(&cached, &contents)?;
+    write_file(&dest, &contents)?;
+    Ok((dest, FetchOutcome::Downloaded))
+}
+
+pub fn run(args: &[String]) {
+    let usage = "Usage: main fetch [year] [day] [--force] [--base-url url]";
+    let (Some(Ok(year)), Some(Ok(day))) = (
+        args.first().map(|y| y.parse::<usize>()),
+        args.get(1).map(|d| d.parse::<usize>()),
+    ) else {
+        println!("{usage}");
+        return;
+    };
+    let force = args.contains(&"--force".to_owned());
+    match fetch_input(
+        || AocClient::from_env(args),
+        year,
+        day,
+        &default_inputs_dir(),
+        &AnswerCache::default_dir(),
+        force,
+    ) {
+        Ok((path, FetchOutcome::AlreadyPresent)) => println!(
+            "{} already exists, use --force to download it again",
+            path.display()
+        ),
+        Ok((path, FetchOutcome::FromCache)) => {
+            println!("Restored {} from the cache", path.display())
+        }
+        Ok((path, FetchOutcome::Downloaded)) => println!("Downloaded {}", path.display()),
+        Err(msg) => println!("Failed to fetch {year} day {day}: {msg}"),
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::mock_http::{MockServer, RecordedRequest};
+    use crate::test_dir::temp_dir;
+    use std::time::{Duration, Instant};
+
+    fn respond(req: &RecordedRequest) -> (u16, String) {
+        match req.path.as_str() {
+            "/2023/day/5/input" => (200, "seeds: 79 14 55 13\n".to_owned()),
+            _ => (404, "404 Not Found".to_owned()),
+        }
+    }
+
+    #[test]
+    fn test_fetch_and_reuse() {
+        let server = MockServer::start(respond);
+        let dir = temp_dir("fetch-reuse");
+        let (inputs, cache) = (dir.join("inputs"), dir.join("cache"));
+        let url = server.url();
+        let client = || Ok(AocClient::new(&url, "abc", cache.clone(), Duration::ZERO));
+
+        let (path, outcome) = fetch_input(client, 2023, 5, &inputs, &cache, false).unwrap();
+        assert_eq!(outcome, FetchOutcome::Downloaded);
+        assert_eq!(path, inputs.join("2023").join("day-05.txt"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "seeds: 79 14 55 13\n");
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].header("cookie"), Some("session=abc"));
+
+        // Hand edits are kept unless forced
+        fs::write(&path, "edited").unwrap();
+        let (_, outcome) = fetch_input(client, 2023, 5, &inputs, &cache, false).unwrap();
+        assert_eq!(outcome, FetchOutcome::AlreadyPresent);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "edited");
+
+        fs::remove_file(&path).unwrap();
+        let (_, outcome) = fetch_input(client, 2023, 5, &inputs, &cache, false).unwrap();
+        assert_eq!(outcome, FetchOutcome::FromCache);
+        assert_eq!(server.requests().len(), 1);
+
+        fs::write(&path, "edited").unwrap();
+        let (_, outcome) = fetch_input(client, 2023, 5, &inputs, &cache, true).unwrap();
+        assert_eq!(outcome, FetchOutcome::Downloaded);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "seeds: 79 14 55 13\n");
+        assert_eq!(server.requests().len(), 2);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_fetch_without_session() {
+        let dir = temp_dir("fetch-session");
+        let (inputs, cache) = (dir.join("inputs"), dir.join("cache"));
+        let no_session = || Err("Set AOC_SESSION to your session cookie".to_owned());
+        let path = input_path(&inputs, 2023, 5);
+        let cached = input_path(&cache.join("inputs"), 2023, 5);
+
+        assert!(fetch_input(no_session, 2023, 5, &inputs, &cache, false).is_err());
+        write_file(&cached, "seeds: 79 14 55 13\n").unwrap();
+        let (_, outcome) = fetch_input(no_session, 2023, 5, &inputs, &cache, false).unwrap();
+        assert_eq!(outcome, FetchOutcome::FromCache);
+        let (_, outcome) = fetch_input(no_session, 2023, 5, &inputs, &cache, false).unwrap();
+        assert_eq!(outcome, FetchOutcome::AlreadyPresent);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "seeds: 79 14 55 13\n");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_fetch_errors() {
+        let server = MockServer::start(respond);
+        let dir = temp_dir("fetch-errors");
+        let (inputs, cache) = (dir.join("inputs"), dir.join("cache"));
+        let url = server.url();
+        let client = || Ok(AocClient::new(&url, "abc", cache.clone(), Duration::ZERO));
+
+        assert!(fetch_input(client, 2023, 6, &inputs, &cache, false).is_err());
+        assert!(!input_path(&inputs, 2023, 6).exists());
+        assert!(fetch_input(client, 2023, 26, &inputs, &cache, false).is_err());
+        assert_eq!(server.requests().len(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let server = MockServer::start(respond);
+        let dir = temp_dir("fetch-rate");
+        let (inputs, cache) = (dir.join("inputs"), dir.join("cache"));
+        let interval = Duration::from_millis(200);
+        let url = server.url();
+        let client = || Ok(AocClient::new(&url, "abc", cache.clone(), interval));
+
+        let start = Instant::now();
+        fetch_input(client, 2023, 5, &inputs, &cache, true).unwrap();
+        fetch_input(client, 2023, 5, &inputs, &cache, true).unwrap();
+        assert!(start.elapsed() >= interval);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,238 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use raqote::DrawTarget;
+
+use crate::arg_value;
+use crate::visualize::{pixel_rgb, Visualize};
+
+// The export args that need a value after them
+const VALUE_ARGS: [&str; 4] = ["--png", "--gif", "--stride", "--size"];
+
+/// Renders a visualization without a display, to PNG files and/or a GIF.
+#[derive(Debug, Clone)]
+pub struct ExportConfig {
+    pub png_dir: Option<PathBuf>,
+    pub gif_path: Option<PathBuf>,
+    /// Only every `frame_stride`th frame is written. The last frame always is.
+    pub frame_stride: usize,
+    pub width: usize,
+    pub height: usize,
+    pub gif_frame_delay_ms: u64,
+}
+
+impl ExportConfig {
+    /// Reads `--png dir`, `--gif file`, `--stride n` and `--size WxH`. Returns
+    /// None unless at least one of `--png` or `--gif` is given.
+    pub fn from_args(args: &[String]) -> Result<Option<Self>, String> {
+        if let Some(name) = args.last().filter(|a| VALUE_ARGS.contains(&a.as_str())) {
+            return Err(format!("Missing value for {name}"));
+        }
+        let png_dir = arg_value(args, "--png").map(PathBuf::from);
+        let gif_path = arg_value(args, "--gif").map(PathBuf::from);
+        if png_dir.is_none() && gif_path.is_none() {
+            return Ok(None);
+        }
+        let frame_stride = match arg_value(args, "--stride") {
+            Some(s) => s
+                .parse()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or(format!("Bad stride: {s}"))?,
+            None => 1,
+        };
+        let (width, height) = match arg_value(args, "--size") {
+            Some(s) => s
+                .split_once('x')
+                .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                .filter(|(w, h): &(usize, usize)| *w > 0 && *h > 0 && *w <= 4096 && *h <= 4096)
+                .ok_or(format!("Bad size, expected WxH: {s}"))?,
+            None => (750, 750),
+        };
+        Ok(Some(Self {
+            png_dir,
+            gif_path,
+            frame_stride,
+            width,
+            height,
+            gif_frame_delay_ms: 20,
+        }))
+    }
+}
+
+fn to_rgba(dt: &DrawTarget) -> Vec<u8> {
+    let mut rgba = Vec::with_capacity(4 * dt.get_data().len());
+    for &pixel in dt.get_data() {
+        let (r, g, b) = pixel_rgb(pixel);
+        rgba.extend([r, g, b, 0xff]);
+    }
+    rgba
+}
+
+struct FrameWriter {
+    config: ExportConfig,
+    gif: Option<gif::Encoder<BufWriter<File>>>,
+    num_written: usize,
+}
+
+impl FrameWriter {
+    pub fn new(config: &ExportConfig) -> Result<Self, String> {
+        if let Some(dir) = &config.png_dir {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+        }
+        let gif = match &config.gif_path {
+            Some(path) => {
+                let file = File::create(path)
+                    .map_err(|e| format!("Failed to create {}: {e}", path.display()))?;
+                let mut encoder = gif::Encoder::new(
+                    BufWriter::new(file),
+                    config.width as u16,
+                    config.height as u16,
+                    &[],
+                )
+                .map_err(|e| format!("Failed to start GIF: {e}"))?;
+                encoder
+                    .set_repeat(gif::Repeat::Infinite)
+                    .map_err(|e| format!("Failed to start GIF: {e}"))?;
+                Some(encoder)
+            }
+            None => None,
+        };
+        Ok(Self {
+            config: config.clone(),
+            gif,
+            num_written: 0,
+        })
+    }
+
+    pub fn write(&mut self, dt: &DrawTarget) -> Result<(), String> {
+        if let Some(dir) = &self.config.png_dir {
+            let path = dir.join(format!("frame-{:05}.png", self.num_written));
+            dt.write_png(&path)
+                .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+        }
+        if let Some(encoder) = self.gif.as_mut() {
+            let mut rgba = to_rgba(dt);
+            let mut frame = gif::Frame::from_rgba_speed(
+                self.config.width as u16,
+                self.config.height as u16,
+                &mut rgba,
+                10,
+            );
+            frame.delay = (self.config.gif_frame_delay_ms / 10).max(1) as u16;
+            encoder
+                .write_frame(&frame)
+                .map_err(|e| format!("Failed to write GIF frame: {e}"))?;
+        }
+        self.num_written += 1;
+        Ok(())
+    }
+}
+
+/// Runs the visualization to completion, writing frames as configured.
+/// Returns the number of frames written.
+pub fn export(vis: &mut impl Visualize, config: &ExportConfig) -> Result<usize, String> {
+    let mut writer = FrameWriter::new(config)?;
+    let mut dt = DrawTarget::new(config.width as i32, config.height as i32);
+
+    let mut frame = 0;
+    let mut running = true;
+    while running {
+        if frame > 0 {
+            running = vis.advance();
+        }
+        // Skipped frames still have to be drawn so that only changes
+        // need drawing each time
+        vis.redraw(&mut dt);
+        if frame % config.frame_stride == 0 || !running {
+            writer.write(&dt)?;
+        }
+        frame += 1;
+    }
+    Ok(writer.num_written)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::test_dir::temp_dir;
+    use crate::visualize::{fill_cells, solid, GridScale};
+
+    /// Fills one more cell of a 4x4 grid each frame
+    struct Filler {
+        num_filled: usize,
+    }
+
+    impl Visualize for Filler {
+        fn title(&self) -> String {
+            "Filler".to_owned()
+        }
+
+        fn advance(&mut self) -> bool {
+            self.num_filled += 1;
+            self.num_filled < 16
+        }
+
+        fn draw(&self, dt: &mut DrawTarget) {
+            let scale = GridScale::new(dt, 4, 4);
+            let cells = (0..self.num_filled).map(|i| (i % 4, i / 4));
+            fill_cells(dt, &scale, cells, &solid(0xff, 0xff, 0x00, 0x00));
+        }
+    }
+
+    fn args(s: &str) -> Vec<String> {
+        s.split(' ').map(|a| a.to_owned()).collect()
+    }
+
+    #[test]
+    fn test_config_from_args() {
+        assert!(ExportConfig::from_args(&args("in.txt 20"))
+            .unwrap()
+            .is_none());
+        let config = ExportConfig::from_args(&args("in.txt --gif a.gif --stride 3 --size 64x32"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(config.gif_path, Some(PathBuf::from("a.gif")));
+        assert_eq!(config.png_dir, None);
+        assert_eq!(
+            (config.frame_stride, config.width, config.height),
+            (3, 64, 32)
+        );
+        assert!(ExportConfig::from_args(&args("--png")).is_err());
+        assert!(ExportConfig::from_args(&args("--png d --stride 0")).is_err());
+        assert!(ExportConfig::from_args(&args("--png d --size 10by10")).is_err());
+    }
+
+    #[test]
+    fn test_export_png_and_gif() {
+        let dir = temp_dir("export");
+        let config = ExportConfig {
+            png_dir: Some(dir.join("frames")),
+            gif_path: Some(dir.join("anim.gif")),
+            frame_stride: 4,
+            width: 40,
+            height: 40,
+            gif_frame_delay_ms: 20,
+        };
+        fs::create_dir_all(&dir).unwrap();
+
+        // Frames 0, 4, 8, 12 and 16, which is also the last
+        let num_frames = export(&mut Filler { num_filled: 0 }, &config).unwrap();
+        assert_eq!(num_frames, 5);
+        assert!(dir.join("frames").join("frame-00004.png").exists());
+        assert!(!dir.join("frames").join("frame-00005.png").exists());
+
+        let mut decoder = gif::DecodeOptions::new()
+            .read_info(File::open(dir.join("anim.gif")).unwrap())
+            .unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (40, 40));
+        let mut num_gif_frames = 0;
+        while decoder.read_next_frame().unwrap().is_some() {
+            num_gif_frames += 1;
+        }
+        assert_eq!(num_gif_frames, 5);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,7 +1,16 @@
 use std::env;
 use std::fs::File;
 use std::io::{self, prelude::*, BufReader};
+use std::path::{Path, PathBuf};
 
+use answer_cache::AnswerCache;
+use answers::Answers;
+
+pub const YEAR: usize = 2023;
+
+mod answer_cache;
+mod answers;
+mod aoc_client;
 mod dec_01_trebuchet;
 mod dec_02_cube_conundrum;
 mod dec_03_gear_ratios;
@@ -13,6 +22,17 @@ mod dec_08_haunted_wasteland;
 mod dec_09_mirage_maintenance;
 mod dec_10_pipe_maze;
 mod dec_11_cosmic_expansion;
+mod fetch;
+mod frame_export;
+#[cfg(test)]
+mod mock_http;
+mod server;
+mod submit;
+mod term_render;
+#[cfg(test)]
+mod test_dir;
+mod visualize;
+mod watch;
 
 pub fn load_file_lines(path: &str) -> io::Result<Vec<String>> {
     let mut lines = Vec::<_>::new();
@@ -29,14 +49,45 @@ pub fn load_file_lines(path: &str) -> io::Result<Vec<String>> {
     Ok(lines)
 }
 
-struct ProgramOption {
+/// The value given after a `name` argument, if there is one.
+pub fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|a| a == name)
+        .and_then(|i| args.get(i + 1))
+        .map(|v| v.as_str())
+}
+
+pub struct ProgramOption {
     pub names: Vec<String>,
-    pub func: fn(&[String]) -> (),
+    pub day: usize,
+    pub version: u32,
+    pub func: fn(&[String]) -> Answers,
     pub hint: String,
+    /// The parts a run with these args (after the input) answers, or `None`
+    /// if the args do more than pick the answer and the solver has to run.
+    pub cached_parts: fn(&[String]) -> Option<Vec<usize>>,
+    /// Args that keep the solver from opening a window, for unattended runs,
+    /// or `None` if it can't run without one.
+    pub headless_args: Option<&'static [&'static str]>,
+    /// Args that make the solver answer part 1 or part 2, for days that
+    /// answer one per run.
+    pub part_args: [&'static [&'static str]; 2],
+    /// The only args a client of the server may pass, as they just pick
+    /// the answer rather than name files or open windows.
+    pub server_args: &'static [&'static str],
+}
+
+/// For days whose every arg is for display, so only a bare run is cached.
+fn both_parts(args: &[String]) -> Option<Vec<usize>> {
+    args.is_empty().then(|| vec![1, 2])
 }
 
 fn print_help(options: &[ProgramOption]) {
-    println!("Usage: main [problem] [input file]");
+    println!("Usage: main [run] [problem] [input file] [--no-cache] [--watch]");
+    println!("       main cache [ls, clear] [day]");
+    println!("       main serve [--port port]");
+    println!("       main fetch [year] [day] [--force] [--base-url url]");
+    println!("       main submit [year] [day] [part] [answer] [--base-url url]");
     println!("Arguments: ");
     for opt in options {
         print!(" [");
@@ -49,7 +100,7 @@ fn print_help(options: &[ProgramOption]) {
     }
 }
 
-fn foo(args: &[String]) {
+fn foo(args: &[String]) -> Answers {
     println!("Yay");
     let mut sep = "";
     for a in args {
@@ -57,6 +108,76 @@ fn foo(args: &[String]) {
         sep = ", ";
     }
     println!();
+    Answers::default()
+}
+
+fn run_cache_command(args: &[String]) {
+    let mut cache = match AnswerCache::open(&AnswerCache::default_dir()) {
+        Ok(cache) => cache,
+        Err(e) => {
+            println!("Failed to open answer cache: {e}");
+            return;
+        }
+    };
+    let day = args.get(1).and_then(|d| d.parse().ok());
+    match args.first().map(|a| a.as_str()) {
+        Some("ls") => {
+            for e in cache
+                .entries()
+                .iter()
+                .filter(|e| day.is_none() || day == Some(e.day))
+            {
+                println!(
+                    "{} day {:2} part {} (v{}, input {:016x}): {}",
+                    e.year, e.day, e.part, e.version, e.input_hash, e.answer
+                );
+            }
+        }
+        Some("clear") => match cache.clear(day) {
+            Ok(n) => println!("Removed {n} cached answers"),
+            Err(e) => println!("Failed to clear answer cache: {e}"),
+        },
+        _ => println!("Usage: main cache [ls, clear] [day]"),
+    }
+}
+
+fn run_solver(opt: &ProgramOption, args: &[String], use_cache: bool, cache_dir: &Path) -> Answers {
+    let Some(expected_parts) = (opt.cached_parts)(&args[1..]) else {
+        return (opt.func)(args);
+    };
+    let input_hash = match answer_cache::hash_input(&args[0], &args[1..]) {
+        Ok(hash) => hash,
+        Err(e) => {
+            println!("WARNING: Not caching, failed to hash {}: {e}", args[0]);
+            return (opt.func)(args);
+        }
+    };
+    let mut cache = match AnswerCache::open(cache_dir) {
+        Ok(cache) => cache,
+        Err(e) => {
+            println!("WARNING: Not caching, failed to open answer cache: {e}");
+            return (opt.func)(args);
+        }
+    };
+
+    if use_cache {
+        let cached = cache.lookup(YEAR, opt.day, input_hash, opt.version);
+        if cached.has_parts(&expected_parts) {
+            for (part, value) in cached.parts() {
+                println!("Part {part} (cached): {value}");
+            }
+            return cached;
+        }
+    }
+
+    let answers = (opt.func)(args);
+    // A run that stopped early or had warnings is solved again next time
+    if answers.has_parts(&expected_parts) && answers.diagnostics.is_empty() {
+        if let Err(e) = cache.store(YEAR, opt.day, input_hash, opt.version, &answers) {
+            println!("WARNING: Failed to write answer cache: {e}");
+        }
+    }
+    answers
 }
 
 #[macro_export]
@@ -72,79 +193,324 @@ macro_rules! cmdset {
     };
 }
 
-fn main() {
-    let options = vec![
+fn watch_solver(opt: &ProgramOption, args: &[String]) {
+    // Any argument naming a file is watched, not just the input
+    let paths: Vec<PathBuf> = args
+        .iter()
+        .map(PathBuf::from)
+        .filter(|p| p.is_file())
+        .collect();
+    watch::watch_files(paths, |paths| {
+        watch::clear_screen();
+        let answers = answers::solve_catching_panics(opt.func, args);
+        println!();
+        println!("Answers:");
+        if answers.is_empty() {
+            println!("- None");
+        }
+        for (part, value) in answers.parts() {
+            println!("- Part {part}: {value}");
+        }
+        if !answers.diagnostics.is_empty() {
+            println!("Diagnostics:");
+            for msg in answers.diagnostics.iter() {
+                println!("- {msg}");
+            }
+        }
+        let names: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+        println!();
+        println!("Watching {} (Ctrl-C to stop)", names.join(", "));
+    });
+}
+
+fn program_options() -> Vec<ProgramOption> {
+    vec![
         ProgramOption {
             names: cmdset!["foo"],
+            day: 0,
+            version: 0,
             func: foo,
             hint: String::from("[file]"),
+            cached_parts: |_| None,
+            headless_args: Some(&[]),
+            part_args: [&[], &[]],
+            server_args: &[],
         },
         ProgramOption {
             names: cmdset!["day-01", "1", "trebuchet"],
+            day: 1,
+            version: dec_01_trebuchet::VERSION,
             func: dec_01_trebuchet::run,
-            hint: String::from("[file] [part_1, part_2]"),
+            hint: String::from("[file] [part_1, part_2] [debug] [color] [--words file]"),
+            cached_parts: dec_01_trebuchet::cached_parts,
+            headless_args: Some(&[]),
+            part_args: [&["part_1"], &["part_2"]],
+            server_args: &["part_1", "part_2"],
         },
         ProgramOption {
             names: cmdset!["day-02", "2", "cube-conundrum"],
+            day: 2,
+            version: dec_02_cube_conundrum::VERSION,
             func: dec_02_cube_conundrum::run,
             hint: String::from("[file] [debug]"),
+            cached_parts: both_parts,
+            headless_args: Some(&[]),
+            part_args: [&[], &[]],
+            server_args: &[],
         },
         ProgramOption {
             names: cmdset!["day-03", "3", "gear-ratios"],
+            day: 3,
+            version: dec_03_gear_ratios::VERSION,
             func: dec_03_gear_ratios::run,
             hint: String::from("[file] [debug]"),
+            cached_parts: both_parts,
+            headless_args: Some(&[]),
+            part_args: [&[], &[]],
+            server_args: &[],
         },
         ProgramOption {
             names: cmdset!["day-04", "4", "scratchcards"],
+            day: 4,
+            version: dec_04_scratchcards::VERSION,
             func: dec_04_scratchcards::run,
             hint: String::from("[file]"),
+            cached_parts: both_parts,
+            headless_args: Some(&[]),
+            part_args: [&[], &[]],
+            server_args: &[],
         },
         ProgramOption {
             names: cmdset!["day-05", "5", "almanac"],
+            day: 5,
+            version: dec_05_fertilizer::VERSION,
             func: dec_05_fertilizer::run,
-            hint: String::from("[file] [debug]"),
+            hint: String::from("[file] [debug] [verify] [lint] [--from category] [--to category]"),
+            cached_parts: both_parts,
+            headless_args: Some(&[]),
+            part_args: [&[], &[]],
+            server_args: &[],
         },
         ProgramOption {
             names: cmdset!["day-06", "6", "wait-for-it"],
+            day: 6,
+            version: dec_06_wait_for_it::VERSION,
             func: dec_06_wait_for_it::run,
-            hint: String::from("[file]"),
+            hint: String::from("[file] [--accel n] [--start-speed n]"),
+            cached_parts: dec_06_wait_for_it::cached_parts,
+            headless_args: Some(&[]),
+            part_args: [&[], &[]],
+            server_args: &[],
         },
         ProgramOption {
             names: cmdset!["day-07", "7", "camel-cards"],
+            day: 7,
+            version: dec_07_camel_cards::VERSION,
             func: dec_07_camel_cards::run,
-            hint: String::from("[file] [debug]"),
+            hint: String::from("[file] [debug] [--rules file]"),
+            cached_parts: both_parts,
+            headless_args: Some(&[]),
+            part_args: [&[], &[]],
+            server_args: &[],
         },
         ProgramOption {
             names: cmdset!["day-08", "8", "haunted-wasteland"],
+            day: 8,
+            version: dec_08_haunted_wasteland::VERSION,
             func: dec_08_haunted_wasteland::run,
-            hint: String::from("[file]"),
+            hint: String::from(
+                "[file] [--export-dot file] [--dot-starts name,name] [--dot-reachable]",
+            ),
+            cached_parts: both_parts,
+            headless_args: Some(&[]),
+            part_args: [&[], &[]],
+            server_args: &[],
         },
         ProgramOption {
             names: cmdset!["day-09", "9", "mirage-maintenance"],
+            day: 9,
+            version: dec_09_mirage_maintenance::VERSION,
             func: dec_09_mirage_maintenance::run,
-            hint: String::from("[file]"),
+            hint: String::from("[file] [--steps k]"),
+            cached_parts: both_parts,
+            headless_args: Some(&[]),
+            part_args: [&[], &[]],
+            server_args: &[],
         },
         ProgramOption {
             names: cmdset!["day-10", "10", "pipe-maze"],
+            day: 10,
+            version: dec_10_pipe_maze::VERSION,
             func: dec_10_pipe_maze::run,
-            hint: String::from("[file] [frame_period_ms]"),
+            hint: String::from(
+                "[file] [frame_period_ms] [--render window, term, none] [--start-pipe c] [-t] [--png dir] [--gif file] [--stride n] [--size WxH]",
+            ),
+            cached_parts: dec_10_pipe_maze::cached_parts,
+            headless_args: Some(&["--render", "none"]),
+            part_args: [&[], &[]],
+            server_args: &[],
         },
         ProgramOption {
             names: cmdset!["day-11", "11", "cosmic-exp"],
+            day: 11,
+            version: dec_11_cosmic_expansion::VERSION,
             func: dec_11_cosmic_expansion::run,
-            hint: String::from("[file] [num_iterations]"),
+            hint: String::from("[file] [expansion factors...]"),
+            cached_parts: dec_11_cosmic_expansion::cached_parts,
+            headless_args: Some(&[]),
+            part_args: [&[], &[]],
+            server_args: &["2", "1000000"],
         },
-    ];
-    let args: Vec<String> = env::args().skip(1).collect();
+    ]
+}
+
+fn main() {
+    let options = program_options();
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|a| a == "cache") {
+        run_cache_command(&args[1..]);
+        return;
+    }
+    if args.first().is_some_and(|a| a == "fetch") {
+        fetch::run(&args[1..]);
+        return;
+    }
+    if args.first().is_some_and(|a| a == "submit") {
+        submit::run(&args[1..], &options);
+        return;
+    }
+    if args.first().is_some_and(|a| a == "serve") {
+        server::run(&args[1..], options);
+        return;
+    }
+    if args.first().is_some_and(|a| a == "run") {
+        args.remove(0);
+    }
+    let use_cache = !args.contains(&"--no-cache".to_owned());
+    let do_watch = args.contains(&"--watch".to_owned());
+    args.retain(|a| a != "--no-cache" && a != "--watch");
     if args.len() < 2 {
         print_help(&options);
         return;
     }
     let cmd = &args[0];
     if let Some(opt) = options.iter().find(|&o| o.names.contains(cmd)) {
-        (opt.func)(&args[1..]);
+        if do_watch {
+            watch_solver(opt, &args[1..]);
+        } else {
+            run_solver(opt, &args[1..], use_cache, &AnswerCache::default_dir());
+        }
     } else {
         println!("Unrecognized argument: {cmd}");
         print_help(&options);
     }
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::test_dir::temp_dir;
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static NUM_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+    // Stops before part 2, like closing the day 10 window early
+    fn aborted_solver(_args: &[String]) -> Answers {
+        NUM_RUNS.fetch_add(1, Ordering::SeqCst);
+        let mut answers = Answers::default();
+        answers.set(1, 4);
+        answers
+    }
+
+    fn solver(_args: &[String]) -> Answers {
+        NUM_RUNS.fetch_add(1, Ordering::SeqCst);
+        Answers::new(4, 8)
+    }
+
+    fn option(func: fn(&[String]) -> Answers) -> ProgramOption {
+        ProgramOption {
+            names: cmdset!["test"],
+            day: 24,
+            version: 1,
+            func,
+            hint: String::from("[file] [debug]"),
+            cached_parts: both_parts,
+            headless_args: Some(&[]),
+            part_args: [&[], &[]],
+            server_args: &[],
+        }
+    }
+
+    #[test]
+    fn test_arg_value() {
+        let args: Vec<String> = ["in.txt", "--from", "seed", "--to"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        assert_eq!(arg_value(&args, "--from"), Some("seed"));
+        assert_eq!(arg_value(&args, "--to"), None);
+        assert_eq!(arg_value(&args, "--steps"), None);
+    }
+
+    #[test]
+    fn test_run_solver_cache() {
+        let dir = temp_dir("run-cache");
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.txt").display().to_string();
+        fs::write(&input, "input").unwrap();
+        let bare = [input.clone()];
+        let runs = || NUM_RUNS.load(Ordering::SeqCst);
+        let start = runs();
+
+        // Partial answers are neither stored nor accepted as a hit
+        run_solver(&option(aborted_solver), &bare, true, &dir);
+        run_solver(&option(aborted_solver), &bare, true, &dir);
+        assert_eq!(runs(), start + 2);
+        assert_eq!(
+            run_solver(&option(solver), &bare, true, &dir),
+            Answers::new(4, 8)
+        );
+        assert_eq!(runs(), start + 3);
+        assert_eq!(
+            run_solver(&option(solver), &bare, true, &dir),
+            Answers::new(4, 8)
+        );
+        assert_eq!(runs(), start + 3);
+
+        // Display args always run the solver
+        let args = [input, "debug".to_owned()];
+        run_solver(&option(solver), &args, true, &dir);
+        run_solver(&option(solver), &args, true, &dir);
+        assert_eq!(runs(), start + 5);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_dot_export_on_repeat_run() {
+        let dir = temp_dir("run-dot");
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.txt").display().to_string();
+        fs::write(
+            &input,
+            "LLR\n\nAAA = (BBB, BBB)\nBBB = (AAA, ZZZ)\nZZZ = (ZZZ, ZZZ)\n",
+        )
+        .unwrap();
+        let dot_path = dir.join("network.dot");
+        let day_8 = program_options().into_iter().find(|o| o.day == 8).unwrap();
+        let args = [
+            input,
+            "--export-dot".to_owned(),
+            dot_path.display().to_string(),
+        ];
+        for _ in 0..2 {
+            let answers = run_solver(&day_8, &args, true, &dir);
+            assert_eq!(answers.get(1), Some("6"));
+            assert!(fs::read_to_string(&dot_path)
+                .unwrap()
+                .starts_with("digraph"));
+            fs::remove_file(&dot_path).unwrap();
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
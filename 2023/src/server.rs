@@ -0,0 +1,594 @@
+use std::env;
+use std::fs;
+use std::io::{self, prelude::*, BufReader};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::answers::{self, Answers};
+use crate::{ProgramOption, YEAR};
+
+static REQUEST_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+const MAX_HEADER_BYTES: usize = 16 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub max_body_bytes: usize,
+    pub solve_timeout: Duration,
+    pub read_timeout: Duration,
+    /// Solver threads allowed at once, counting ones still running after
+    /// their request timed out.
+    pub max_solves: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 1024 * 1024,
+            solve_timeout: Duration::from_secs(30),
+            read_timeout: Duration::from_secs(5),
+            max_solves: 4,
+        }
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    body: String,
+}
+
+impl Response {
+    pub fn json(status: u16, body: String) -> Self {
+        Self { status, body }
+    }
+
+    pub fn error(status: u16, msg: &str) -> Self {
+        Self::json(status, format!("{{\"error\":{}}}", json_string(msg)))
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            408 => "Request Timeout",
+            411 => "Length Required",
+            413 => "Payload Too Large",
+            431 => "Request Header Fields Too Large",
+            503 => "Service Unavailable",
+            504 => "Gateway Timeout",
+            _ => "Internal Server Error",
+        }
+    }
+
+    pub fn write_to(&self, stream: &mut impl Write) -> io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            self.reason(),
+            self.body.len(),
+            self.body
+        )?;
+        stream.flush()
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_option(value: Option<&str>) -> String {
+    value.map(json_string).unwrap_or_else(|| "null".to_owned())
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(b) => {
+                        out.push(b);
+                        i += 2;
+                    }
+                    Err(_) => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn parse_request(reader: &mut impl BufRead, config: &ServerConfig) -> Result<Request, Response> {
+    let mut head = Vec::new();
+    loop {
+        let mut line = Vec::new();
+        let num_read = reader
+            .read_until(b'\n', &mut line)
+            .map_err(|_| Response::error(408, "Timed out reading request"))?;
+        if num_read == 0 {
+            return Err(Response::error(400, "Connection closed mid-request"));
+        }
+        head.extend_from_slice(&line);
+        if head.len() > MAX_HEADER_BYTES {
+            return Err(Response::error(431, "Request headers too large"));
+        }
+        if line == b"\r\n" || line == b"\n" {
+            break;
+        }
+    }
+    let head = String::from_utf8_lossy(&head);
+    let mut lines = head.lines();
+    let request_line: Vec<&str> = lines.next().unwrap_or("").split(' ').collect();
+    if request_line.len() != 3 {
+        return Err(Response::error(400, "Malformed request line"));
+    }
+
+    let mut content_length = None;
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = Some(
+                    value
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|_| Response::error(400, "Bad Content-Length"))?,
+                );
+            }
+        }
+    }
+
+    let method = request_line[0].to_owned();
+    let body = match content_length {
+        Some(n) if n > config.max_body_bytes => {
+            return Err(Response::error(
+                413,
+                &format!("Body exceeds {} bytes", config.max_body_bytes),
+            ));
+        }
+        Some(n) => {
+            let mut body = vec![0; n];
+            reader
+                .read_exact(&mut body)
+                .map_err(|_| Response::error(408, "Timed out reading body"))?;
+            body
+        }
+        None if method == "POST" => return Err(Response::error(411, "Content-Length required")),
+        None => Vec::new(),
+    };
+
+    let (path, query_str) = request_line[1]
+        .split_once('?')
+        .unwrap_or((request_line[1], ""));
+    let query = query_str
+        .split('&')
+        .filter(|kv| !kv.is_empty())
+        .map(|kv| {
+            let (k, v) = kv.split_once('=').unwrap_or((kv, ""));
+            (percent_decode(k), percent_decode(v))
+        })
+        .collect();
+
+    Ok(Request {
+        method,
+        path: path.to_owned(),
+        query,
+        body,
+    })
+}
+
+/// One of the `max_solves` solver threads, given back when the solver
+/// finishes rather than when its request stops waiting.
+struct SolveSlot(Arc<AtomicUsize>);
+
+impl SolveSlot {
+    fn claim(active: &Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < max).then_some(n + 1)
+            })
+            .ok()?;
+        Some(Self(Arc::clone(active)))
+    }
+}
+
+impl Drop for SolveSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn list_days(options: &[ProgramOption]) -> Response {
+    let mut days = Vec::new();
+    for opt in options.iter().filter(|o| o.day > 0) {
+        let names: Vec<String> = opt.names.iter().map(|n| json_string(n)).collect();
+        days.push(format!(
+            "{{\"year\":{},\"day\":{},\"version\":{},\"names\":[{}],\"hint\":{}}}",
+            YEAR,
+            opt.day,
+            opt.version,
+            names.join(","),
+            json_string(&opt.hint)
+        ));
+    }
+    Response::json(200, format!("{{\"days\":[{}]}}", days.join(",")))
+}
+
+fn solve(
+    opt: &ProgramOption,
+    req: &Request,
+    config: &ServerConfig,
+    active_solves: &Arc<AtomicUsize>,
+) -> Response {
+    let Some(headless_args) = opt.headless_args else {
+        return Response::error(501, &format!("Day {} needs a window", opt.day));
+    };
+    // Anything else could name a file to read or write on the server
+    let query_args: Vec<String> = req
+        .query
+        .iter()
+        .filter(|(k, _)| k == "arg")
+        .map(|(_, v)| v.clone())
+        .collect();
+    if let Some(arg) = query_args
+        .iter()
+        .find(|a| !opt.server_args.contains(&a.as_str()))
+    {
+        return Response::error(400, &format!("Day {} doesn't take {arg} here", opt.day));
+    }
+    let Some(slot) = SolveSlot::claim(active_solves, config.max_solves) else {
+        return Response::error(503, "Too many solvers running, try again later");
+    };
+    // Solvers read their input from a file, so hand them a temporary one
+    let input_path = env::temp_dir().join(format!(
+        "aoc-serve-{}-{}.txt",
+        std::process::id(),
+        REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    if let Err(e) = fs::write(&input_path, &req.body) {
+        return Response::error(500, &format!("Failed to stage input: {e}"));
+    }
+    // Ahead of the query's args, so there's never a window on the server
+    let mut args = vec![input_path.display().to_string()];
+    args.extend(headless_args.iter().map(|a| a.to_string()));
+    args.extend(query_args);
+
+    let func = opt.func;
+    let (sender, receiver) = mpsc::channel();
+    let start = Instant::now();
+    thread::spawn(move || {
+        let answers = answers::solve_catching_panics(func, &args);
+        let _ = fs::remove_file(&args[0]);
+        let _ = sender.send(answers);
+        drop(slot);
+    });
+    let answers: Answers = match receiver.recv_timeout(config.solve_timeout) {
+        Ok(answers) => answers,
+        Err(_) => {
+            // The solver thread can't be killed; it cleans up after itself and
+            // frees its slot when done
+            return Response::error(
+                504,
+                &format!("Solver exceeded {} ms", config.solve_timeout.as_millis()),
+            );
+        }
+    };
+    let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    let diagnostics: Vec<String> = answers.diagnostics.iter().map(|d| json_string(d)).collect();
+    Response::json(
+        200,
+        format!(
+            "{{\"year\":{},\"day\":{},\"version\":{},\"answers\":{{\"part_1\":{},\"part_2\":{}}},\"timings\":{{\"total_ms\":{:.3}}},\"diagnostics\":[{}]}}",
+            YEAR,
+            opt.day,
+            opt.version,
+            json_option(answers.get(1)),
+            json_option(answers.get(2)),
+            elapsed_ms,
+            diagnostics.join(",")
+        ),
+    )
+}
+
+fn route(
+    req: &Request,
+    options: &[ProgramOption],
+    config: &ServerConfig,
+    active_solves: &Arc<AtomicUsize>,
+) -> Response {
+    let segments: Vec<&str> = req.path.split('/').filter(|s| !s.is_empty()).collect();
+    match segments.as_slice() {
+        ["days"] => match req.method.as_str() {
+            "GET" => list_days(options),
+            _ => Response::error(405, "Use GET for /days"),
+        },
+        ["solve", year, day] => {
+            if req.method != "POST" {
+                return Response::error(405, "Use POST for /solve");
+            }
+            let (Ok(year), Ok(day)) = (year.parse::<usize>(), day.parse::<usize>()) else {
+                return Response::error(400, "Year and day must be numbers");
+            };
+            match options.iter().find(|o| o.day == day && year == YEAR) {
+                Some(opt) if day > 0 => solve(opt, req, config, active_solves),
+                _ => Response::error(404, &format!("No solver for {year} day {day}")),
+            }
+        }
+        _ => Response::error(404, "Unknown route"),
+    }
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    options: &[ProgramOption],
+    config: &ServerConfig,
+    active_solves: &Arc<AtomicUsize>,
+) {
+    let _ = stream.set_read_timeout(Some(config.read_timeout));
+    let mut writer = match stream.try_clone() {
+        Ok(s) => s,
+        Err(_) => return,
+    };
+    let mut reader = BufReader::new(stream);
+    let response = match parse_request(&mut reader, config) {
+        Ok(req) => route(&req, options, config, active_solves),
+        Err(response) => response,
+    };
+    if let Err(e) = response.write_to(&mut writer) {
+        println!("WARNING: Failed to send response: {e}");
+    }
+}
+
+pub struct Server {
+    listener: TcpListener,
+    options: Arc<Vec<ProgramOption>>,
+    config: Arc<ServerConfig>,
+    active_solves: Arc<AtomicUsize>,
+}
+
+impl Server {
+    pub fn bind(addr: &str, options: Vec<ProgramOption>, config: ServerConfig) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            options: Arc::new(options),
+            config: Arc::new(config),
+            active_solves: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serves requests forever, one thread per connection.
+    pub fn run(&self) {
+        for stream in self.listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let options = Arc::clone(&self.options);
+                    let config = Arc::clone(&self.config);
+                    let active_solves = Arc::clone(&self.active_solves);
+                    thread::spawn(move || {
+                        handle_connection(stream, &options, &config, &active_solves)
+                    });
+                }
+                Err(e) => println!("WARNING: Failed to accept connection: {e}"),
+            }
+        }
+    }
+}
+
+pub fn run(args: &[String], options: Vec<ProgramOption>) {
+    let port = match args.iter().position(|a| a == "--port") {
+        Some(i) => match args.get(i + 1).map(|p| p.parse::<u16>()) {
+            Some(Ok(port)) => port,
+            _ => {
+                println!("Usage: main serve [--port port]");
+                return;
+            }
+        },
+        None => 8023,
+    };
+    let server = match Server::bind(
+        &format!("127.0.0.1:{port}"),
+        options,
+        ServerConfig::default(),
+    ) {
+        Ok(server) => server,
+        Err(e) => {
+            println!("Failed to bind port {port}: {e}");
+            return;
+        }
+    };
+    match server.local_addr() {
+        Ok(addr) => println!("Serving solvers on http://{addr}"),
+        Err(e) => println!("WARNING: Failed to read bound address: {e}"),
+    }
+    println!("- GET  /days");
+    println!("- POST /solve/{{year}}/{{day}}[?arg=..]");
+    server.run();
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::cmdset;
+    use rstest::rstest;
+
+    fn slow_solver(_args: &[String]) -> Answers {
+        thread::sleep(Duration::from_millis(500));
+        Answers::new(1, 2)
+    }
+
+    fn test_options() -> Vec<ProgramOption> {
+        let mut options = crate::program_options();
+        options.push(ProgramOption {
+            names: cmdset!["slow"],
+            day: 25,
+            version: 1,
+            func: slow_solver,
+            hint: String::from("[file]"),
+            cached_parts: |_| None,
+            headless_args: Some(&[]),
+            part_args: [&[], &[]],
+            server_args: &[],
+        });
+        options
+    }
+
+    fn start_server(config: ServerConfig) -> SocketAddr {
+        let server = Server::bind("127.0.0.1:0", test_options(), config).unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+        addr
+    }
+
+    fn send(addr: SocketAddr, request: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response.split_once("\r\n\r\n").unwrap().1.to_owned();
+        (status, body)
+    }
+
+    fn post(addr: SocketAddr, path: &str, body: &str) -> (u16, String) {
+        send(
+            addr,
+            &format!(
+                "POST {path} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            ),
+        )
+    }
+
+    #[test]
+    fn test_list_days() {
+        let addr = start_server(ServerConfig::default());
+        let (status, body) = send(addr, "GET /days HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert_eq!(status, 200);
+        assert!(body.contains("\"day\":6,"));
+        assert!(body.contains("\"wait-for-it\""));
+        assert!(!body.contains("\"foo\""));
+    }
+
+    #[test]
+    fn test_solve() {
+        let addr = start_server(ServerConfig::default());
+        let input = "Time:      7  15   30\nDistance:  9  40  200\n";
+        let (status, body) = post(addr, "/solve/2023/6", input);
+        assert_eq!(status, 200);
+        assert!(body.contains("\"answers\":{\"part_1\":\"288\",\"part_2\":\"71503\"}"));
+        assert!(body.contains("\"diagnostics\":[]"));
+    }
+
+    #[test]
+    fn test_solve_with_args() {
+        let addr = start_server(ServerConfig::default());
+        let input = "two1nine\neightwothree\n";
+        let (status, body) = post(addr, "/solve/2023/1?arg=part_2", input);
+        assert_eq!(status, 200);
+        assert!(body.contains("\"part_1\":null,\"part_2\":\"112\""));
+    }
+
+    #[test]
+    fn test_solve_headless() {
+        let addr = start_server(ServerConfig::default());
+        let input = "-L|F7\n7S-7|\nL|7||\n-L-J|\nL|-JF\n";
+        let (status, body) = post(addr, "/solve/2023/10", input);
+        assert_eq!(status, 200);
+        assert!(body.contains("\"answers\":{\"part_1\":\"4\",\"part_2\":\"1\"}"));
+    }
+
+    #[rstest]
+    #[case("/solve/2023/1?arg=--words&arg=/etc/passwd")]
+    #[case("/solve/2023/8?arg=--export-dot&arg=/tmp/network.dot")]
+    #[case("/solve/2023/10?arg=--render&arg=window")]
+    #[case("/solve/2023/1?arg=part_2&arg=debug")]
+    fn test_solve_rejects_args(#[case] path: &str) {
+        let addr = start_server(ServerConfig::default());
+        let (status, body) = post(addr, path, "");
+        assert_eq!(status, 400);
+        assert!(body.contains("doesn't take"));
+    }
+
+    #[test]
+    fn test_solve_reports_panics() {
+        let addr = start_server(ServerConfig::default());
+        let (status, body) = post(addr, "/solve/2023/6", "garbage");
+        assert_eq!(status, 200);
+        assert!(body.contains("Solver panicked"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let config = ServerConfig {
+            max_body_bytes: 64,
+            solve_timeout: Duration::from_millis(50),
+            ..ServerConfig::default()
+        };
+        let addr = start_server(config);
+        assert_eq!(post(addr, "/solve/2023/6", &"1".repeat(65)).0, 413);
+        assert_eq!(post(addr, "/solve/2023/26", "").0, 404);
+        assert_eq!(post(addr, "/solve/2022/6", "").0, 404);
+        assert_eq!(post(addr, "/solve/2023/six", "").0, 400);
+        assert_eq!(post(addr, "/days", "").0, 405);
+        assert_eq!(post(addr, "/solve/2023/25", "").0, 504);
+    }
+
+    #[test]
+    fn test_solve_limit() {
+        let config = ServerConfig {
+            solve_timeout: Duration::from_millis(50),
+            max_solves: 1,
+            ..ServerConfig::default()
+        };
+        let addr = start_server(config);
+        assert_eq!(post(addr, "/solve/2023/25", "").0, 504);
+        // The timed out solver still holds the only slot
+        assert_eq!(post(addr, "/solve/2023/25", "").0, 503);
+        thread::sleep(Duration::from_millis(600));
+        let input = "Time:      7  15   30\nDistance:  9  40  200\n";
+        assert_eq!(post(addr, "/solve/2023/6", input).0, 200);
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("a%20b+c"), "a b c");
+        assert_eq!(percent_decode("100%"), "100%");
+    }
+}
@@ -0,0 +1,434 @@
+use std::fs::{self, File};
+use std::io::{self, prelude::*, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::answer_cache::AnswerCache;
+use crate::aoc_client::AocClient;
+use crate::fetch;
+use crate::{run_solver, ProgramOption, YEAR};
+
+const HISTORY_FILE_NAME: &str = "submissions.tsv";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hint {
+    TooHigh,
+    TooLow,
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Correct,
+    Wrong(Hint),
+    RateLimited(Option<Duration>),
+    /// The part was already solved, or part 1 isn't solved yet
+    WrongLevel,
+    Unknown(String),
+}
+
+impl Verdict {
+    pub fn from_response(body: &str) -> Self {
+        lazy_static! {
+            static ref RE_WAIT: Regex =
+                Regex::new(r"You have (?:(?<min>\d+)m )?(?<sec>\d+)s left to wait").unwrap();
+            static ref RE_TAGS: Regex = Regex::new(r"<[^>]*>").unwrap();
+        };
+        if body.contains("That's the right answer") {
+            Self::Correct
+        } else if body.contains("That's not the right answer") {
+            if body.contains("too high") {
+                Self::Wrong(Hint::TooHigh)
+            } else if body.contains("too low") {
+                Self::Wrong(Hint::TooLow)
+            } else {
+                Self::Wrong(Hint::None)
+            }
+        } else if body.contains("You gave an answer too recently") {
+            Self::RateLimited(RE_WAIT.captures(body).map(|cap| {
+                let min: u64 = cap.name("min").map_or(0, |m| m.as_str().parse().unwrap());
+                let sec: u64 = cap["sec"].parse().unwrap();
+                Duration::from_secs(60 * min + sec)
+            }))
+        } else if body.contains("You don't seem to be solving the right level") {
+            Self::WrongLevel
+        } else {
+            let text = RE_TAGS.replace_all(body, " ");
+            Self::Unknown(text.split_whitespace().collect::<Vec<&str>>().join(" "))
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Correct => "correct",
+            Self::Wrong(Hint::TooHigh) => "too-high",
+            Self::Wrong(Hint::TooLow) => "too-low",
+            Self::Wrong(Hint::None) => "wrong",
+            Self::RateLimited(_) => "rate-limited",
+            Self::WrongLevel => "wrong-level",
+            Self::Unknown(_) => "unknown",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attempt {
+    pub year: usize,
+    pub day: usize,
+    pub part: usize,
+    pub answer: String,
+    /// One of the `Verdict` labels
+    pub verdict: String,
+    pub time_s: u64,
+}
+
+impl Attempt {
+    pub fn from_str(line: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 6 {
+            return Err(format!("Expected 6 fields in history line: {line}"));
+        }
+        let bad_field = |name: &str| format!("Bad {name} in history line: {line}");
+        Ok(Self {
+            year: fields[0].parse().map_err(|_| bad_field("year"))?,
+            day: fields[1].parse().map_err(|_| bad_field("day"))?,
+            part: fields[2].parse().map_err(|_| bad_field("part"))?,
+            answer: fields[3].to_owned(),
+            verdict: fields[4].to_owned(),
+            time_s: fields[5].parse().map_err(|_| bad_field("time"))?,
+        })
+    }
+
+    pub fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            self.year, self.day, self.part, self.answer, self.verdict, self.time_s
+        )
+    }
+}
+
+pub struct SubmissionHistory {
+    path: PathBuf,
+    attempts: Vec<Attempt>,
+}
+
+impl SubmissionHistory {
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(HISTORY_FILE_NAME);
+        let mut attempts = Vec::new();
+        if path.exists() {
+            let reader = BufReader::new(File::open(&path)?);
+            for line in reader.lines() {
+                match Attempt::from_str(&line?) {
+                    Ok(attempt) => attempts.push(attempt),
+                    Err(msg) => println!("WARNING: {msg}"),
+                }
+            }
+        }
+        Ok(Self { path, attempts })
+    }
+
+    pub fn attempts(&self) -> &[Attempt] {
+        &self.attempts
+    }
+
+    /// Explains why an answer shouldn't be sent, if the history already rules it out.
+    pub fn check(&self, year: usize, day: usize, part: usize, answer: &str) -> Option<String> {
+        let value: Option<i64> = answer.parse().ok();
+        for a in self
+            .attempts
+            .iter()
+            .filter(|a| a.year == year && a.day == day && a.part == part)
+        {
+            let known_value: Option<i64> = a.answer.parse().ok();
+            match a.verdict.as_str() {
+                "correct" => return Some(format!("Already solved with {}", a.answer)),
+                "too-high" | "too-low" | "wrong" if a.answer == answer => {
+                    return Some(format!("{answer} was already rejected ({})", a.verdict))
+                }
+                "too-high" if value.is_some() && value >= known_value => {
+                    return Some(format!(
+                        "{answer} is not below {}, which was too high",
+                        a.answer
+                    ))
+                }
+                "too-low" if value.is_some() && value <= known_value => {
+                    return Some(format!(
+                        "{answer} is not above {}, which was too low",
+                        a.answer
+                    ))
+                }
+                _ => (),
+            }
+        }
+        None
+    }
+
+    pub fn record(
+        &mut self,
+        year: usize,
+        day: usize,
+        part: usize,
+        answer: &str,
+        verdict: &Verdict,
+    ) -> io::Result<()> {
+        let attempt = Attempt {
+            year,
+            day,
+            part,
+            answer: answer.to_owned(),
+            verdict: verdict.label().to_owned(),
+            time_s: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        };
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", attempt.to_line())?;
+        self.attempts.push(attempt);
+        Ok(())
+    }
+}
+
+/// Submits an answer unless the history says it's pointless, and records the verdict.
+pub fn submit_answer(
+    client: &AocClient,
+    history: &mut SubmissionHistory,
+    year: usize,
+    day: usize,
+    part: usize,
+    answer: &str,
+) -> Result<Verdict, String> {
+    if !(1..=2).contains(&part) {
+        return Err(format!("There is no part {part}"));
+    }
+    if answer.is_empty() || answer.contains(['\t', '\n']) {
+        return Err(format!("Refusing to submit {answer:?}"));
+    }
+    if let Some(reason) = history.check(year, day, part, answer) {
+        return Err(format!("Not submitting: {reason}"));
+    }
+    let part_str = part.to_string();
+    let body = client.post_form(
+        &format!("/{year}/day/{day}/answer"),
+        &[("level", &part_str), ("answer", answer)],
+    )?;
+    let verdict = Verdict::from_response(&body);
+    if let Err(e) = history.record(year, day, part, answer, &verdict) {
+        println!("WARNING: Failed to write submission history: {e}");
+    }
+    Ok(verdict)
+}
+
+/// The args for a run that answers `part` without opening a window, if the
+/// solver can run without one.
+fn solver_args(opt: &ProgramOption, input: &str, part: usize) -> Option<Vec<String>> {
+    let headless_args = opt.headless_args?;
+    let mut args = vec![input.to_owned()];
+    if let Some(part_args) = opt.part_args.get(part.wrapping_sub(1)) {
+        args.extend(part_args.iter().map(|a| a.to_string()));
+    }
+    args.extend(headless_args.iter().map(|a| a.to_string()));
+    Some(args)
+}
+
+fn solver_answer(
+    options: &[ProgramOption],
+    year: usize,
+    day: usize,
+    part: usize,
+) -> Option<String> {
+    if year != YEAR {
+        println!("Only {YEAR} solvers are available here, give the answer explicitly");
+        return None;
+    }
+    let Some(opt) = options.iter().find(|o| o.day == day) else {
+        println!("No solver for day {day}, give the answer explicitly");
+        return None;
+    };
+    let input = fetch::input_path(&fetch::default_inputs_dir(), year, day);
+    if !input.exists() {
+        println!("Missing {}, fetch it first", input.display());
+        return None;
+    }
+    let Some(args) = solver_args(opt, &input.display().to_string(), part) else {
+        println!("The day {day} solver needs a window, give the answer explicitly");
+        return None;
+    };
+    let answers = run_solver(opt, &args, true, &AnswerCache::default_dir());
+    let answer = answers.get(part).map(|a| a.to_owned());
+    if answer.is_none() {
+        println!("The day {day} solver didn't produce a part {part} answer");
+    }
+    answer
+}
+
+pub fn run(args: &[String], options: &[ProgramOption]) {
+    let usage = "Usage: main submit [year] [day] [part] [answer] [--base-url url]";
+    let (Some(Ok(year)), Some(Ok(day)), Some(Ok(part))) = (
+        args.first().map(|y| y.parse::<usize>()),
+        args.get(1).map(|d| d.parse::<usize>()),
+        args.get(2).map(|p| p.parse::<usize>()),
+    ) else {
+        println!("{usage}");
+        return;
+    };
+    let explicit_answer = args
+        .get(3)
+        .filter(|a| !a.starts_with("--"))
+        .map(|a| a.to_owned());
+    let Some(answer) = explicit_answer.or_else(|| solver_answer(options, year, day, part)) else {
+        return;
+    };
+
+    let client = match AocClient::from_env(args) {
+        Ok(client) => client,
+        Err(msg) => {
+            println!("{msg}");
+            return;
+        }
+    };
+    let mut history = match SubmissionHistory::open(&AnswerCache::default_dir()) {
+        Ok(history) => history,
+        Err(e) => {
+            println!("Failed to open submission history: {e}");
+            return;
+        }
+    };
+
+    for a in history
+        .attempts()
+        .iter()
+        .filter(|a| a.year == year && a.day == day && a.part == part)
+    {
+        println!("- Previously submitted {} ({})", a.answer, a.verdict);
+    }
+    println!("Submitting {answer} for {year} day {day} part {part}");
+    match submit_answer(&client, &mut history, year, day, part, &answer) {
+        Ok(Verdict::Correct) => println!("Correct!"),
+        Ok(Verdict::Wrong(Hint::TooHigh)) => println!("Wrong, too high"),
+        Ok(Verdict::Wrong(Hint::TooLow)) => println!("Wrong, too low"),
+        Ok(Verdict::Wrong(Hint::None)) => println!("Wrong"),
+        Ok(Verdict::RateLimited(Some(wait))) => {
+            println!("Rate limited, wait {} s", wait.as_secs())
+        }
+        Ok(Verdict::RateLimited(None)) => println!("Rate limited"),
+        Ok(Verdict::WrongLevel) => println!("Part {part} is already solved or not unlocked"),
+        Ok(Verdict::Unknown(text)) => println!("Unrecognized response: {text}"),
+        Err(msg) => println!("{msg}"),
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::mock_http::{MockServer, RecordedRequest};
+    use crate::test_dir::temp_dir;
+    use rstest::rstest;
+
+    fn respond(req: &RecordedRequest) -> (u16, String) {
+        let body = if req.body.contains("answer=42") {
+            "<main><article><p>That's the right answer! You are one gold star closer.</p></article></main>"
+        } else if req.body.contains("answer=100") {
+            "<main><article><p>That's not the right answer; your answer is too high.</p></article></main>"
+        } else {
+            "<main><article><p>You gave an answer too recently. You have 38s left to wait.</p></article></main>"
+        };
+        (200, body.to_owned())
+    }
+
+    #[rstest]
+    #[case(
+        "<p>That's the right answer! You are one gold star closer.</p>",
+        Verdict::Correct
+    )]
+    #[case(
+        "<p>That's not the right answer; your answer is too high. Please wait one minute.</p>",
+        Verdict::Wrong(Hint::TooHigh)
+    )]
+    #[case(
+        "<p>That's not the right answer; your answer is too low.</p>",
+        Verdict::Wrong(Hint::TooLow)
+    )]
+    #[case(
+        "<p>That's not the right answer. If you're stuck, ask for hints.</p>",
+        Verdict::Wrong(Hint::None)
+    )]
+    #[case(
+        "<p>You gave an answer too recently; you have to wait. You have 5m 3s left to wait.</p>",
+        Verdict::RateLimited(Some(Duration::from_secs(303)))
+    )]
+    #[case(
+        "<p>You gave an answer too recently. You have 38s left to wait.</p>",
+        Verdict::RateLimited(Some(Duration::from_secs(38)))
+    )]
+    #[case(
+        "<p>You don't seem to be solving the right level.  Did you already complete it?</p>",
+        Verdict::WrongLevel
+    )]
+    #[case("<p>Something <b>else</b></p>", Verdict::Unknown("Something else".to_owned()))]
+    fn test_parse_verdict(#[case] body: &str, #[case] verdict: Verdict) {
+        assert_eq!(Verdict::from_response(body), verdict);
+    }
+
+    #[test]
+    fn test_submit_and_history() {
+        let server = MockServer::start(respond);
+        let dir = temp_dir("submit-history");
+        let client = AocClient::new(&server.url(), "abc", dir.clone(), Duration::ZERO);
+        let mut history = SubmissionHistory::open(&dir).unwrap();
+
+        let verdict = submit_answer(&client, &mut history, 2023, 6, 1, "100").unwrap();
+        assert_eq!(verdict, Verdict::Wrong(Hint::TooHigh));
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/2023/day/6/answer");
+        assert_eq!(requests[0].body, "level=1&answer=100");
+
+        // Known wrong answers never reach the server
+        assert!(submit_answer(&client, &mut history, 2023, 6, 1, "100").is_err());
+        assert!(submit_answer(&client, &mut history, 2023, 6, 1, "150").is_err());
+        assert_eq!(server.requests().len(), 1);
+
+        let verdict = submit_answer(&client, &mut history, 2023, 6, 2, "7").unwrap();
+        assert_eq!(verdict, Verdict::RateLimited(Some(Duration::from_secs(38))));
+
+        let verdict = submit_answer(&client, &mut history, 2023, 6, 1, "42").unwrap();
+        assert_eq!(verdict, Verdict::Correct);
+        assert!(submit_answer(&client, &mut history, 2023, 6, 1, "43").is_err());
+
+        let history = SubmissionHistory::open(&dir).unwrap();
+        let verdicts: Vec<&str> = history
+            .attempts()
+            .iter()
+            .map(|a| a.verdict.as_str())
+            .collect();
+        assert_eq!(verdicts, vec!["too-high", "rate-limited", "correct"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[rstest]
+    #[case(1, 1, Some(vec!["in.txt", "part_1"]))]
+    #[case(1, 2, Some(vec!["in.txt", "part_2"]))]
+    #[case(6, 2, Some(vec!["in.txt"]))]
+    #[case(10, 1, Some(vec!["in.txt", "--render", "none"]))]
+    fn test_solver_args(
+        #[case] day: usize,
+        #[case] part: usize,
+        #[case] expected: Option<Vec<&str>>,
+    ) {
+        let options = crate::program_options();
+        let opt = options.iter().find(|o| o.day == day).unwrap();
+        let expected = expected.map(|e| e.iter().map(|a| a.to_string()).collect());
+        assert_eq!(solver_args(opt, "in.txt", part), expected);
+    }
+}
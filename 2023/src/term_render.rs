@@ -0,0 +1,163 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use raqote::DrawTarget;
+
+use crate::visualize::{pixel_rgb, Visualize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    TrueColor,
+    Ansi256,
+}
+
+impl ColorMode {
+    /// True color if the terminal advertises it, otherwise the 256 color palette.
+    pub fn detect() -> Self {
+        match env::var("COLORTERM") {
+            Ok(v) if v == "truecolor" || v == "24bit" => Self::TrueColor,
+            _ => Self::Ansi256,
+        }
+    }
+
+    fn escape(&self, (r, g, b): (u8, u8, u8), background: bool) -> String {
+        let layer = if background { 48 } else { 38 };
+        match self {
+            Self::TrueColor => format!("\x1b[{layer};2;{r};{g};{b}m"),
+            Self::Ansi256 => {
+                // Nearest color in the 6x6x6 cube
+                let level = |c: u8| (c as u16 * 5 + 127) / 255;
+                let idx = 16 + 36 * level(r) + 6 * level(g) + level(b);
+                format!("\x1b[{layer};5;{idx}m")
+            }
+        }
+    }
+}
+
+/// Columns and rows of the controlling terminal, falling back to 80x24.
+pub fn terminal_size() -> (usize, usize) {
+    let from_env = |name: &str| env::var(name).ok().and_then(|v| v.parse().ok());
+    if let (Some(cols), Some(rows)) = (from_env("COLUMNS"), from_env("LINES")) {
+        return (cols, rows);
+    }
+    let stty = File::open("/dev/tty").ok().and_then(|tty| {
+        Command::new("stty")
+            .arg("size")
+            .stdin(Stdio::from(tty))
+            .output()
+            .ok()
+    });
+    if let Some(output) = stty {
+        let text = String::from_utf8_lossy(&output.stdout);
+        let values: Vec<usize> = text
+            .split_whitespace()
+            .filter_map(|v| v.parse().ok())
+            .collect();
+        if let [rows, cols] = values[..] {
+            if rows > 0 && cols > 0 {
+                return (cols, rows);
+            }
+        }
+    }
+    (80, 24)
+}
+
+/// Turns a frame into terminal text, two pixels per character using the
+/// upper half block. The text starts at the top left of the screen.
+pub fn frame_to_ansi(dt: &DrawTarget, mode: ColorMode) -> String {
+    let width = dt.width() as usize;
+    let height = dt.height() as usize;
+    let data = dt.get_data();
+    let mut out = String::with_capacity(width * height * 8);
+    out.push_str("\x1b[H");
+    let mut last_colors = None;
+    for y in (0..height).step_by(2) {
+        for x in 0..width {
+            let top = pixel_rgb(data[y * width + x]);
+            let bottom = if y + 1 < height {
+                pixel_rgb(data[(y + 1) * width + x])
+            } else {
+                (0, 0, 0)
+            };
+            if last_colors != Some((top, bottom)) {
+                out.push_str(&mode.escape(top, false));
+                out.push_str(&mode.escape(bottom, true));
+                last_colors = Some((top, bottom));
+            }
+            out.push('▀');
+        }
+        out.push_str("\x1b[0m\r\n");
+        last_colors = None;
+    }
+    out
+}
+
+/// Animates in the terminal, redrawing in place, until the simulation finishes.
+pub fn animate_in_terminal(vis: &mut impl Visualize, frame_period_ms: u64, mode: ColorMode) {
+    let (cols, rows) = terminal_size();
+    // Leave a few rows under the frame for what the solver prints, so the
+    // screen doesn't scroll and break the in-place redraw
+    let mut dt = DrawTarget::new(cols as i32, 2 * (rows as i32 - 4).max(1));
+    let mut stdout = io::stdout();
+    let _ = write!(stdout, "\x1b[2J\x1b[?25l");
+
+    let mut running = true;
+    let mut first_frame = true;
+    while running {
+        let frame_start = Instant::now();
+        if !first_frame {
+            running = vis.advance();
+        }
+        first_frame = false;
+        vis.redraw(&mut dt);
+        let _ = stdout.write_all(frame_to_ansi(&dt, mode).as_bytes());
+        let _ = stdout.flush();
+
+        let elapsed = frame_start.elapsed();
+        let period = Duration::from_millis(frame_period_ms);
+        if running && elapsed < period {
+            sleep(period - elapsed);
+        }
+    }
+    let _ = write!(stdout, "\x1b[?25h");
+    let _ = stdout.flush();
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::visualize::{fill_cells, solid, GridScale, BACKGROUND};
+
+    fn half_red_frame() -> DrawTarget {
+        let mut dt = DrawTarget::new(4, 4);
+        dt.clear(BACKGROUND);
+        let scale = GridScale::new(&dt, 2, 2);
+        fill_cells(
+            &mut dt,
+            &scale,
+            [(0, 0), (1, 0)].into_iter(),
+            &solid(0xff, 0xff, 0x00, 0x00),
+        );
+        dt
+    }
+
+    #[test]
+    fn test_true_color_frame() {
+        let text = frame_to_ansi(&half_red_frame(), ColorMode::TrueColor);
+        let lines: Vec<&str> = text.split("\r\n").filter(|l| !l.is_empty()).collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("\x1b[38;2;255;0;0m\x1b[48;2;255;0;0m▀▀▀▀"));
+        assert!(lines[1].contains("\x1b[38;2;0;0;0m\x1b[48;2;0;0;0m▀▀▀▀"));
+    }
+
+    #[test]
+    fn test_256_color_frame() {
+        let text = frame_to_ansi(&half_red_frame(), ColorMode::Ansi256);
+        assert!(text.starts_with("\x1b[H\x1b[38;5;196m\x1b[48;5;196m"));
+        assert!(text.contains("\x1b[38;5;16m\x1b[48;5;16m"));
+    }
+}
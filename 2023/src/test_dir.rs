@@ -0,0 +1,13 @@
+//! Scratch directories for tests that touch the filesystem.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// A path under the system temp dir, unique to this test process. Whatever
+/// an earlier run left there is removed, but the directory isn't created.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("aoc-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
@@ -0,0 +1,445 @@
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
+use raqote::{DrawOptions, DrawTarget, PathBuilder, SolidSource, Source};
+
+use crate::arg_value;
+use crate::frame_export::{export, ExportConfig};
+use crate::term_render::{animate_in_terminal, ColorMode};
+
+/// What every frame is drawn over.
+pub const BACKGROUND: SolidSource = SolidSource {
+    r: 0x00,
+    g: 0x00,
+    b: 0x00,
+    a: 0xff,
+};
+
+/// A simulation that can be animated one frame at a time.
+pub trait Visualize {
+    fn title(&self) -> String;
+
+    /// Moves the simulation forward one frame. Returns false once there is
+    /// nothing left to animate.
+    fn advance(&mut self) -> bool;
+
+    fn draw(&self, dt: &mut DrawTarget);
+
+    /// Brings a target showing the previously drawn frame up to date. The
+    /// drivers always pass the same target, so simulations where only a few
+    /// cells change per frame can repaint just those. The default clears
+    /// and draws everything.
+    fn redraw(&mut self, dt: &mut DrawTarget) {
+        dt.clear(BACKGROUND);
+        self.draw(dt);
+    }
+
+    /// Steps back one frame. Returns false if there's no history to rewind.
+    fn rewind(&mut self) -> bool {
+        false
+    }
+
+    /// Describes what was drawn at a pixel of the draw target, for mouse clicks.
+    fn inspect(&self, _dt: &DrawTarget, _x: f32, _y: f32) -> Option<String> {
+        None
+    }
+
+    /// Lines of text to show over the frame, like counters.
+    fn overlay(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationConfig {
+    pub width: usize,
+    pub height: usize,
+    pub frame_period_ms: u64,
+    /// Draws how long each frame took in the corner of the window
+    pub show_timing: bool,
+}
+
+impl Default for AnimationConfig {
+    fn default() -> Self {
+        Self {
+            width: 750,
+            height: 750,
+            frame_period_ms: 20,
+            show_timing: false,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct FrameTimer {
+    pub clear: u128,
+    pub advance: u128,
+    pub draw: u128,
+    pub win_update: u128,
+}
+
+/// Maps the cells of a grid onto a draw target.
+pub struct GridScale {
+    pub cell_width: f32,
+    pub cell_height: f32,
+}
+
+impl GridScale {
+    pub fn new(dt: &DrawTarget, num_cols: usize, num_rows: usize) -> Self {
+        Self {
+            cell_width: dt.width() as f32 / num_cols as f32,
+            cell_height: dt.height() as f32 / num_rows as f32,
+        }
+    }
+
+    /// The left, top, right and bottom pixel edges of a cell. Cells are
+    /// snapped to whole pixels so that neighbours never share one, which lets
+    /// a single cell be repainted without seams.
+    pub fn bounds(&self, col: usize, row: usize) -> (usize, usize, usize, usize) {
+        (
+            (self.cell_width * col as f32).floor() as usize,
+            (self.cell_height * row as f32).floor() as usize,
+            (self.cell_width * (col + 1) as f32).floor() as usize,
+            (self.cell_height * (row + 1) as f32).floor() as usize,
+        )
+    }
+
+    pub fn rect(&self, pb: &mut PathBuilder, col: usize, row: usize) {
+        let (left, top, right, bottom) = self.bounds(col, row);
+        pb.rect(
+            left as f32,
+            top as f32,
+            (right - left) as f32,
+            (bottom - top) as f32,
+        );
+    }
+
+    /// The `(col, row)` cell under a pixel, which may be past the last cell.
+    pub fn cell_at(&self, x: f32, y: f32) -> (usize, usize) {
+        (
+            (x.max(0.0) / self.cell_width) as usize,
+            (y.max(0.0) / self.cell_height) as usize,
+        )
+    }
+}
+
+pub fn solid(alpha: u8, red: u8, green: u8, blue: u8) -> Source<'static> {
+    Source::Solid(SolidSource::from_unpremultiplied_argb(
+        alpha, red, green, blue,
+    ))
+}
+
+/// Fills every listed `(col, row)` cell with one color.
+pub fn fill_cells(
+    dt: &mut DrawTarget,
+    scale: &GridScale,
+    cells: impl Iterator<Item = (usize, usize)>,
+    source: &Source,
+) {
+    let mut pb = PathBuilder::new();
+    for (col, row) in cells {
+        scale.rect(&mut pb, col, row);
+    }
+    dt.fill(&pb.finish(), source, &DrawOptions::new());
+}
+
+/// Copies cells back from a background target of the same size, replacing
+/// whatever was drawn over them.
+pub fn restore_cells(
+    dt: &mut DrawTarget,
+    background: &DrawTarget,
+    scale: &GridScale,
+    cells: impl Iterator<Item = (usize, usize)>,
+) {
+    let width = dt.width() as usize;
+    let height = dt.height() as usize;
+    let data = dt.get_data_mut();
+    let background = background.get_data();
+    for (col, row) in cells {
+        let (left, top, right, bottom) = scale.bounds(col, row);
+        for y in top..bottom.min(height) {
+            let span = y * width + left.min(width)..y * width + right.min(width);
+            data[span.clone()].copy_from_slice(&background[span]);
+        }
+    }
+}
+
+/// The color of one `DrawTarget` pixel. Frames are drawn over the opaque
+/// background, so the color isn't premultiplied and the alpha can be dropped.
+pub fn pixel_rgb(pixel: u32) -> (u8, u8, u8) {
+    ((pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8)
+}
+
+// 3x5 glyphs, one bit per pixel, top row in the highest bits
+fn glyph(c: char) -> u16 {
+    match c.to_ascii_uppercase() {
+        '0' => 0b111_101_101_101_111,
+        '1' => 0b010_110_010_010_111,
+        '2' => 0b111_001_111_100_111,
+        '3' => 0b111_001_111_001_111,
+        '4' => 0b101_101_111_001_001,
+        '5' => 0b111_100_111_001_111,
+        '6' => 0b111_100_111_101_111,
+        '7' => 0b111_001_010_010_010,
+        '8' => 0b111_101_111_101_111,
+        '9' => 0b111_101_111_001_111,
+        'A' => 0b010_101_111_101_101,
+        'B' => 0b110_101_110_101_110,
+        'C' => 0b011_100_100_100_011,
+        'D' => 0b110_101_101_101_110,
+        'E' => 0b111_100_110_100_111,
+        'F' => 0b111_100_110_100_100,
+        'G' => 0b011_100_101_101_011,
+        'H' => 0b101_101_111_101_101,
+        'I' => 0b111_010_010_010_111,
+        'J' => 0b001_001_001_101_010,
+        'K' => 0b101_101_110_101_101,
+        'L' => 0b100_100_100_100_111,
+        'M' => 0b101_111_111_101_101,
+        'N' => 0b110_101_101_101_101,
+        'O' => 0b010_101_101_101_010,
+        'P' => 0b110_101_110_100_100,
+        'Q' => 0b010_101_101_110_011,
+        'R' => 0b110_101_110_101_101,
+        'S' => 0b011_100_010_001_110,
+        'T' => 0b111_010_010_010_010,
+        'U' => 0b101_101_101_101_111,
+        'V' => 0b101_101_101_101_010,
+        'W' => 0b101_101_111_111_101,
+        'X' => 0b101_101_010_101_101,
+        'Y' => 0b101_101_010_010_010,
+        'Z' => 0b111_001_010_100_111,
+        ':' => 0b000_010_000_010_000,
+        '.' => 0b000_000_000_000_010,
+        '-' => 0b000_000_111_000_000,
+        '/' => 0b001_001_010_100_100,
+        '(' => 0b010_100_100_100_010,
+        ')' => 0b010_001_001_001_010,
+        _ => 0,
+    }
+}
+
+/// Draws text with a built in pixel font, so no system fonts are needed.
+/// Each font pixel is `pixel_size` wide; a line is `6 * pixel_size` tall.
+pub fn draw_text(
+    dt: &mut DrawTarget,
+    text: &str,
+    x: f32,
+    y: f32,
+    pixel_size: f32,
+    source: &Source,
+) {
+    let mut pb = PathBuilder::new();
+    for (i, c) in text.chars().enumerate() {
+        let bits = glyph(c);
+        let glyph_x = x + 4.0 * pixel_size * i as f32;
+        for bit in 0..15 {
+            if bits & (1 << (14 - bit)) != 0 {
+                pb.rect(
+                    glyph_x + pixel_size * (bit % 3) as f32,
+                    y + pixel_size * (bit / 3) as f32,
+                    pixel_size,
+                    pixel_size,
+                );
+            }
+        }
+    }
+    dt.fill(&pb.finish(), source, &DrawOptions::new());
+}
+
+/// Draws lines of text over a dark box in the top left corner.
+pub fn draw_overlay(dt: &mut DrawTarget, lines: &[String]) {
+    let pixel_size = 2.0;
+    let longest = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let mut pb = PathBuilder::new();
+    pb.rect(
+        0.0,
+        0.0,
+        4.0 * pixel_size * longest as f32 + 2.0 * pixel_size,
+        6.0 * pixel_size * lines.len() as f32 + pixel_size,
+    );
+    dt.fill(&pb.finish(), &solid(0xc0, 0, 0, 0), &DrawOptions::new());
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(
+            dt,
+            line,
+            pixel_size,
+            pixel_size + 6.0 * pixel_size * i as f32,
+            pixel_size,
+            &solid(0xff, 0xff, 0xff, 0xff),
+        );
+    }
+}
+
+/// Runs an animation in a window until it's closed or Escape is pressed.
+/// The window keeps showing the last frame after the simulation finishes.
+///
+/// Controls: space pauses, right arrow steps while paused, left arrow
+/// rewinds (if the simulation supports it), +/- change the speed and a
+/// click inspects whatever is under the cursor.
+pub fn animate(vis: &mut impl Visualize, config: &AnimationConfig) {
+    let default_window_refresh_ms = 20;
+    let mut frame_period_ms = u64::max(1, config.frame_period_ms);
+    let mut window = Window::new(
+        &vis.title(),
+        config.width,
+        config.height,
+        WindowOptions::default(),
+    )
+    .unwrap_or_else(|e| {
+        panic!("{e}");
+    });
+
+    let (win_width, win_height) = window.get_size();
+    // The simulation keeps its frame up to date in `frame`, which is copied
+    // to `dt` so the overlay can go on top without ending up in the frame
+    let mut frame = DrawTarget::new(win_width as i32, win_height as i32);
+    let mut dt = DrawTarget::new(win_width as i32, win_height as i32);
+
+    let mut iter: u64 = 1;
+    let mut running = true;
+    let mut paused = false;
+    let mut mouse_was_down = false;
+    let mut inspected: Option<String> = None;
+    let mut timer = FrameTimer::default();
+    let mut needs_redraw = true;
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        let iter_start = Instant::now();
+        let window_refresh_ms = frame_period_ms.min(default_window_refresh_ms);
+        let frames_per_step = u64::max(1, frame_period_ms / window_refresh_ms);
+
+        // Playback controls
+        let mut step = running && !paused && iter.is_multiple_of(frames_per_step);
+        if window.is_key_pressed(Key::Space, KeyRepeat::No) {
+            paused = !paused;
+            needs_redraw = true;
+        }
+        if paused && running && window.is_key_pressed(Key::Right, KeyRepeat::Yes) {
+            step = true;
+        }
+        if window.is_key_pressed(Key::Left, KeyRepeat::Yes) && vis.rewind() {
+            paused = true;
+            running = true;
+            needs_redraw = true;
+        }
+        if window.is_key_pressed(Key::Equal, KeyRepeat::Yes)
+            || window.is_key_pressed(Key::NumPadPlus, KeyRepeat::Yes)
+        {
+            frame_period_ms = u64::max(1, frame_period_ms / 2);
+            needs_redraw = true;
+        }
+        if window.is_key_pressed(Key::Minus, KeyRepeat::Yes)
+            || window.is_key_pressed(Key::NumPadMinus, KeyRepeat::Yes)
+        {
+            frame_period_ms = u64::min(10_000, frame_period_ms * 2);
+            needs_redraw = true;
+        }
+        let mouse_down = window.get_mouse_down(MouseButton::Left);
+        if mouse_down && !mouse_was_down {
+            if let Some((x, y)) = window.get_mouse_pos(MouseMode::Discard) {
+                inspected = vis.inspect(&dt, x, y);
+                if let Some(text) = &inspected {
+                    println!("{text}");
+                }
+                needs_redraw = true;
+            }
+        }
+        mouse_was_down = mouse_down;
+
+        if step {
+            let start_advance = Instant::now();
+            running = vis.advance();
+            timer.advance = start_advance.elapsed().as_micros();
+            needs_redraw = true;
+        }
+
+        if needs_redraw {
+            let start_draw = Instant::now();
+            vis.redraw(&mut frame);
+            timer.draw = start_draw.elapsed().as_micros();
+
+            let start_clear = Instant::now();
+            dt.get_data_mut().copy_from_slice(frame.get_data());
+            timer.clear = start_clear.elapsed().as_micros();
+
+            let mut overlay = vis.overlay();
+            overlay.push(format!(
+                "{} {} MS/FRAME",
+                if paused { "PAUSED" } else { "PLAYING" },
+                frame_period_ms
+            ));
+            if let Some(text) = &inspected {
+                overlay.push(text.clone());
+            }
+            if config.show_timing {
+                overlay.push(format!("CLEAR   {} US", timer.clear));
+                overlay.push(format!("ADVANCE {} US", timer.advance));
+                overlay.push(format!("DRAW    {} US", timer.draw));
+            }
+            draw_overlay(&mut dt, &overlay);
+
+            let start_update = Instant::now();
+            window
+                .update_with_buffer(dt.get_data(), win_width, win_height)
+                .unwrap();
+            timer.win_update = start_update.elapsed().as_micros();
+            needs_redraw = false;
+        } else {
+            window.update();
+        }
+
+        if config.show_timing {
+            print!("\r{:?}", timer);
+        }
+
+        let window_refresh_us = 1000 * window_refresh_ms;
+        let iter_duration = iter_start.elapsed().as_micros() as u64;
+        let wait_time_us = if iter_duration < window_refresh_us {
+            window_refresh_us - iter_duration
+        } else {
+            10
+        };
+        sleep(Duration::from_micros(wait_time_us));
+        iter += 1;
+    }
+}
+
+/// Shows a visualization the way the arguments ask for: `--png`/`--gif` to
+/// write files, `--render term` for the terminal (`--color 256` to avoid
+/// true color), otherwise `--render window`, the default.
+pub fn render(
+    vis: &mut impl Visualize,
+    args: &[String],
+    frame_period_ms: u64,
+) -> Result<(), String> {
+    if let Some(export_config) = ExportConfig::from_args(args)? {
+        let export_config = ExportConfig {
+            gif_frame_delay_ms: frame_period_ms,
+            ..export_config
+        };
+        let num_frames = export(vis, &export_config)?;
+        println!("Wrote {num_frames} frames");
+        return Ok(());
+    }
+    match arg_value(args, "--render") {
+        None | Some("window") => {
+            let config = AnimationConfig {
+                frame_period_ms,
+                show_timing: args.contains(&"-t".to_owned()) || args.contains(&"--time".to_owned()),
+                ..AnimationConfig::default()
+            };
+            animate(vis, &config);
+        }
+        Some("term") => {
+            let mode = match arg_value(args, "--color") {
+                Some("256") => ColorMode::Ansi256,
+                Some("truecolor") => ColorMode::TrueColor,
+                _ => ColorMode::detect(),
+            };
+            animate_in_terminal(vis, frame_period_ms, mode);
+        }
+        Some(other) => return Err(format!("Unknown renderer {other}, use window or term")),
+    }
+    Ok(())
+}
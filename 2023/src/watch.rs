@@ -0,0 +1,92 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+pub const POLL_PERIOD_MS: u64 = 250;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    pub fn read(path: &Path) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        Some(Self {
+            modified: meta.modified().ok(),
+            len: meta.len(),
+        })
+    }
+}
+
+/// Tracks the last seen modification time of a set of files.
+pub struct FileWatcher {
+    paths: Vec<PathBuf>,
+    stamps: Vec<Option<FileStamp>>,
+}
+
+impl FileWatcher {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let stamps = paths.iter().map(|p| FileStamp::read(p)).collect();
+        Self { paths, stamps }
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Returns true if any file was modified, created or removed since the last check.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        for (path, stamp) in self.paths.iter().zip(self.stamps.iter_mut()) {
+            let new_stamp = FileStamp::read(path);
+            if new_stamp != *stamp {
+                *stamp = new_stamp;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+pub fn clear_screen() {
+    print!("\x1b[2J\x1b[H");
+    let _ = io::stdout().flush();
+}
+
+/// Calls `on_change` once, then again every time a watched file changes.
+/// Never returns; stop it with Ctrl-C.
+pub fn watch_files(paths: Vec<PathBuf>, mut on_change: impl FnMut(&[PathBuf])) {
+    let mut watcher = FileWatcher::new(paths);
+    on_change(watcher.paths());
+    loop {
+        sleep(Duration::from_millis(POLL_PERIOD_MS));
+        if watcher.poll() {
+            on_change(watcher.paths());
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_poll_detects_changes() {
+        let path = env::temp_dir().join(format!("aoc-watch-{}.txt", std::process::id()));
+        fs::write(&path, "abc").unwrap();
+        let mut watcher = FileWatcher::new(vec![path.clone()]);
+        assert!(!watcher.poll());
+
+        fs::write(&path, "abcdef").unwrap();
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        fs::remove_file(&path).unwrap();
+        assert!(watcher.poll());
+    }
+}